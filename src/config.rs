@@ -1,23 +1,314 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::Duration;
+
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::terminal::{CursorShape, CursorStyle, LaunchSpec};
+
 pub struct Config {
-    pub color_pallete: ColorPallete,
     pub background_color: wgpu::Color,
     pub scale: f32,
     pub font_size: f32,
-    pub cursor: char,
+    // the cursor style used until a program picks one with DECSCUSR
+    pub cursor_style: CursorStyle,
+    // how long a blinking cursor stays shown and hidden, zero stops it from blinking
+    pub cursor_blink_interval: Duration,
+    // `{title}` is replaced with the title set by the program, `{cwd}` with its working directory
+    pub title_format: String,
+    pub keybindings: Vec<KeyBinding>,
+    // drop notifications from OSC 9, 777 and 99 while the window has focus
+    pub notify_only_when_unfocused: bool,
+    // prefix the window title with the OSC 9;4 progress percentage
+    pub progress_in_title: bool,
+    // let a full reset (`ESC c`) drop the scrollback along with the screen
+    pub reset_clears_scrollback: bool,
+    // how long synchronized output (mode 2026) may hold off drawing
+    pub synchronized_output_timeout: Duration,
+    // keep the window open once the program exits, showing how it ended
+    pub hold: bool,
+    // the command run in the pty, the user's shell by default
+    pub launch: LaunchSpec,
+    // the command line without the working directory, which new windows are started with
+    pub args: Vec<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            color_pallete: ColorPallete::default(),
             background_color: wgpu::Color::BLACK,
             scale: 1.0,
             font_size: 16.0,
-            cursor: '█',
+            cursor_style: CursorStyle::new(CursorShape::Block, true),
+            cursor_blink_interval: Duration::from_millis(530),
+            title_format: "{title}".to_string(),
+            keybindings: vec![
+                KeyBinding::new(
+                    VirtualKeyCode::N,
+                    ModifiersState::CTRL | ModifiersState::SHIFT,
+                    Action::SpawnNewWindow,
+                ),
+                KeyBinding::new(
+                    VirtualKeyCode::Up,
+                    ModifiersState::CTRL | ModifiersState::SHIFT,
+                    Action::PreviousPrompt,
+                ),
+                KeyBinding::new(
+                    VirtualKeyCode::Down,
+                    ModifiersState::CTRL | ModifiersState::SHIFT,
+                    Action::NextPrompt,
+                ),
+                KeyBinding::new(
+                    VirtualKeyCode::G,
+                    ModifiersState::CTRL | ModifiersState::SHIFT,
+                    Action::CopyLastCommandOutput,
+                ),
+                KeyBinding::new(
+                    VirtualKeyCode::V,
+                    ModifiersState::CTRL | ModifiersState::SHIFT,
+                    Action::Paste,
+                ),
+                KeyBinding::new(
+                    VirtualKeyCode::U,
+                    ModifiersState::CTRL | ModifiersState::SHIFT,
+                    Action::UnicodeInput,
+                ),
+            ],
+            notify_only_when_unfocused: false,
+            progress_in_title: true,
+            reset_clears_scrollback: false,
+            synchronized_output_timeout: Duration::from_millis(150),
+            hold: false,
+            launch: LaunchSpec::shell(),
+            args: vec![],
+        }
+    }
+}
+
+impl Config {
+    // the defaults changed by the command line:
+    // `--hold`, `--login`, `-d`/`--working-directory DIR`, `--env KEY=VALUE`, `--unset KEY`,
+    // `--bind KEYS=ACTION`, `--when NAME[=VALUE]` and `-e PROGRAM ARGS...`, which takes the
+    // rest of the line. `--when` limits the binding before it to when the OSC 1337 user
+    // variable is set, or has that value
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+        // bindings from the command line are looked at before the defaults
+        let mut bindings: Vec<KeyBinding> = vec![];
+
+        while let Some(arg) = args.next() {
+            let value = match arg.as_str() {
+                "-d" | "--working-directory" | "--env" | "--unset" | "--bind" | "--when" => {
+                    args.next()
+                }
+                _ => None,
+            };
+
+            let working_directory = matches!(arg.as_str(), "-d" | "--working-directory");
+            if !working_directory {
+                config.args.push(arg.clone());
+                config.args.extend(value.clone());
+            }
+
+            match arg.as_str() {
+                "--hold" => config.hold = true,
+                "--login" => config.launch.login = true,
+                _ if working_directory => config.launch.cwd = value.map(Into::into),
+                "--env" => {
+                    if let Some((key, value)) = value.as_deref().and_then(|e| e.split_once('=')) {
+                        config.launch.env.push((key.to_string(), value.to_string()));
+                    }
+                }
+                "--unset" => config.launch.env_remove.extend(value),
+                "--bind" => match value.as_deref().and_then(KeyBinding::parse) {
+                    Some(binding) => bindings.push(binding),
+                    None => println!("ignoring invalid keybinding"),
+                },
+                "--when" => {
+                    if let (Some(binding), Some(condition)) = (bindings.last_mut(), value) {
+                        binding.condition = Some(Condition::parse(&condition));
+                    }
+                }
+                "-e" => {
+                    if let Some(program) = args.next() {
+                        config.launch.program = program;
+                        config.launch.args = args.by_ref().collect();
+                        config.args.push(config.launch.program.clone());
+                        config.args.extend(config.launch.args.iter().cloned());
+                    }
+                }
+                _ => println!("ignoring unknown argument: {}", arg),
+            }
+        }
+
+        config.keybindings.splice(0..0, bindings);
+        config
+    }
+
+    // the command line for another window like this one, whose command starts in `dir`
+    pub fn new_window_args(&self, dir: Option<&Path>) -> Vec<OsString> {
+        let dir = dir.map(|dir| ["-d".into(), dir.into()]);
+        dir.into_iter()
+            .flatten()
+            .chain(self.args.iter().map(Into::into))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    // opens another window whose shell starts in the current working directory
+    SpawnNewWindow,
+    // jump between prompts marked with OSC 133
+    PreviousPrompt,
+    NextPrompt,
+    CopyLastCommandOutput,
+    // sends the clipboard to the program
+    Paste,
+    // opens an overlay to pick a character by codepoint or name and type it
+    UnicodeInput,
+}
+
+impl Action {
+    fn parse(name: &str) -> Option<Self> {
+        let action = match name {
+            "spawn-new-window" => Action::SpawnNewWindow,
+            "previous-prompt" => Action::PreviousPrompt,
+            "next-prompt" => Action::NextPrompt,
+            "copy-last-command-output" => Action::CopyLastCommandOutput,
+            "paste" => Action::Paste,
+            "unicode-input" => Action::UnicodeInput,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+}
+
+// limits a keybinding to when a user variable set through OSC 1337 has some value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    UserVarSet(String),
+    UserVarEquals(String, String),
+}
+
+impl Condition {
+    // `NAME=VALUE`, or a lone `NAME` for any value
+    fn parse(condition: &str) -> Self {
+        match condition.split_once('=') {
+            Some((name, value)) => Condition::UserVarEquals(name.to_string(), value.to_string()),
+            None => Condition::UserVarSet(condition.to_string()),
+        }
+    }
+
+    pub fn holds(&self, user_vars: &HashMap<String, String>) -> bool {
+        match self {
+            Condition::UserVarSet(name) => user_vars.contains_key(name),
+            Condition::UserVarEquals(name, value) => user_vars.get(name) == Some(value),
         }
     }
 }
 
+#[derive(Debug)]
+pub struct KeyBinding {
+    pub key: VirtualKeyCode,
+    pub modifiers: ModifiersState,
+    pub action: Action,
+    pub condition: Option<Condition>,
+}
+
+impl KeyBinding {
+    pub fn new(key: VirtualKeyCode, modifiers: ModifiersState, action: Action) -> Self {
+        Self {
+            key,
+            modifiers,
+            action,
+            condition: None,
+        }
+    }
+
+    // `ctrl+shift+b=next-prompt`, modifiers and keys are named in lowercase
+    fn parse(binding: &str) -> Option<Self> {
+        let (keys, action) = binding.split_once('=')?;
+        let mut modifiers = ModifiersState::empty();
+        let mut key = None;
+
+        for name in keys.split('+') {
+            match name {
+                "ctrl" => modifiers |= ModifiersState::CTRL,
+                "shift" => modifiers |= ModifiersState::SHIFT,
+                "alt" => modifiers |= ModifiersState::ALT,
+                "super" => modifiers |= ModifiersState::LOGO,
+                _ if key.is_none() => key = Some(key_code(name)?),
+                _ => return None,
+            }
+        }
+
+        Some(Self::new(key?, modifiers, Action::parse(action)?))
+    }
+
+    pub fn matches(
+        &self,
+        key: VirtualKeyCode,
+        modifiers: ModifiersState,
+        user_vars: &HashMap<String, String>,
+    ) -> bool {
+        self.key == key
+            && self.modifiers == modifiers
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.holds(user_vars))
+    }
+}
+
+// the key a keybinding names: letters, digits, `f1` to `f12` and some named keys
+fn key_code(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    let digits = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    let functions = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c {
+            'a'..='z' => Some(letters[c as usize - 'a' as usize]),
+            '0'..='9' => Some(digits[c as usize - '0' as usize]),
+            _ => None,
+        };
+    }
+
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return functions.get(n.checked_sub(1)?).copied();
+    }
+
+    let key = match name {
+        "up" => Up,
+        "down" => Down,
+        "left" => Left,
+        "right" => Right,
+        "home" => Home,
+        "end" => End,
+        "pageup" => PageUp,
+        "pagedown" => PageDown,
+        "insert" => Insert,
+        "delete" => Delete,
+        "enter" => Return,
+        "tab" => Tab,
+        "backspace" => Back,
+        "escape" => Escape,
+        "space" => Space,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
 #[derive(Debug)]
 pub struct ColorPallete {
     pub black: [f32; 4],
@@ -44,3 +335,84 @@ impl Default for ColorPallete {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keybinding_condition() {
+        let args = "--bind ctrl+shift+b=next-prompt --when program=vim --bind alt+f5=paste";
+        let config = Config::from_args(args.split(' ').map(String::from));
+        let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+
+        let binding = &config.keybindings[0];
+        assert_eq!(binding.action, Action::NextPrompt);
+        assert_eq!(
+            binding.condition,
+            Some(Condition::UserVarEquals(
+                "program".to_string(),
+                "vim".to_string()
+            ))
+        );
+
+        let mut user_vars = HashMap::new();
+        assert!(!binding.matches(VirtualKeyCode::B, ctrl_shift, &user_vars));
+
+        user_vars.insert("program".to_string(), "less".to_string());
+        assert!(!binding.matches(VirtualKeyCode::B, ctrl_shift, &user_vars));
+
+        user_vars.insert("program".to_string(), "vim".to_string());
+        assert!(binding.matches(VirtualKeyCode::B, ctrl_shift, &user_vars));
+        assert!(!binding.matches(VirtualKeyCode::B, ModifiersState::CTRL, &user_vars));
+
+        let binding = &config.keybindings[1];
+        assert_eq!(binding.action, Action::Paste);
+        assert!(binding.matches(VirtualKeyCode::F5, ModifiersState::ALT, &HashMap::new()));
+
+        let config = Config::from_args(["--bind".to_string(), "ctrl+x+y=paste".to_string()]);
+        assert_eq!(
+            config.keybindings.len(),
+            Config::default().keybindings.len()
+        );
+    }
+
+    #[test]
+    fn test_from_args() {
+        let args = "--hold -d /tmp --env EDITOR=vi --unset PAGER -e git log --oneline";
+        let config = Config::from_args(args.split(' ').map(String::from));
+
+        assert!(config.hold);
+        assert_eq!(config.launch.cwd, Some("/tmp".into()));
+        assert_eq!(
+            config.launch.env,
+            vec![("EDITOR".to_string(), "vi".to_string())]
+        );
+        assert_eq!(config.launch.env_remove, vec!["PAGER".to_string()]);
+        assert_eq!(config.launch.program, "git");
+        assert_eq!(config.launch.args, vec!["log", "--oneline"]);
+
+        // new windows keep everything but the directory
+        let new_window = "-d /home --hold --env EDITOR=vi --unset PAGER -e git log --oneline";
+        assert_eq!(
+            config.new_window_args(Some(Path::new("/home"))),
+            new_window
+                .split(' ')
+                .map(OsString::from)
+                .collect::<Vec<_>>()
+        );
+        let config = Config::from_args(["-e", "ls", "-d", "/"].map(String::from));
+        assert_eq!(config.launch.cwd, None);
+        assert_eq!(config.new_window_args(None), ["-e", "ls", "-d", "/"]);
+
+        let config = Config::from_args(["--login".to_string()]);
+        assert!(config.launch.login);
+        assert_eq!(
+            config.launch,
+            LaunchSpec {
+                login: true,
+                ..LaunchSpec::shell()
+            }
+        );
+    }
+}
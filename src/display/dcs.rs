@@ -0,0 +1,95 @@
+// answers the DECRQSS and XTGETTCAP queries sent as device control strings
+use crate::terminal::Terminal;
+
+use super::enums::Dcs;
+
+// capabilities reported through XTGETTCAP, `None` marks a boolean capability
+const CAPABILITIES: &[(&str, Option<&str>)] = &[
+    ("TN", Some("xterm-256color")),
+    ("name", Some("xterm-256color")),
+    ("Co", Some("256")),
+    ("colors", Some("256")),
+    ("RGB", Some("8/8/8")),
+    ("Tc", None),
+    ("Ss", Some("\\E[%p1%d q")),
+    ("Se", Some("\\E[2 q")),
+];
+
+// the reply to a device control string, if it is one we answer
+pub fn respond(dcs: &Dcs, terminal: &Terminal) -> Option<String> {
+    match (dcs.intermediates.as_str(), dcs.final_char) {
+        ("$", 'q') => Some(request_status_string(&dcs.data, terminal)),
+        ("+", 'q') => Some(request_termcap(&dcs.data)),
+        _ => None,
+    }
+}
+
+// DECRQSS, `DCS $ q Pt ST`
+fn request_status_string(setting: &str, terminal: &Terminal) -> String {
+    let status = match setting {
+        "m" => Some(format!("{}m", terminal.setting.sgr())),
+        "r" => Some(format!("1;{}r", terminal.height)),
+        " q" => Some(format!("{} q", terminal.cursor_style.decscusr())),
+        _ => None,
+    };
+
+    match status {
+        Some(status) => format!("\x1bP1$r{}\x1b\\", status),
+        None => "\x1bP0$r\x1b\\".to_string(),
+    }
+}
+
+// XTGETTCAP, `DCS + q Pt ST` where Pt is a `;` separated list of hex encoded names
+fn request_termcap(names: &str) -> String {
+    let mut reply = String::new();
+
+    for hex_name in names.split(';') {
+        let capability = decode_hex(hex_name).and_then(|name| {
+            CAPABILITIES
+                .iter()
+                .find(|(capability, _)| *capability == name)
+        });
+
+        match capability {
+            Some((_, Some(value))) => {
+                reply.push_str(&format!("\x1bP1+r{}={}\x1b\\", hex_name, encode_hex(value)))
+            }
+            Some((_, None)) => reply.push_str(&format!("\x1bP1+r{}\x1b\\", hex_name)),
+            None => reply.push_str(&format!("\x1bP0+r{}\x1b\\", hex_name)),
+        }
+    }
+
+    reply
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    String::from_utf8(bytes).ok()
+}
+
+fn encode_hex(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_termcap() {
+        // "Tc" and "colors"
+        assert_eq!(
+            request_termcap("5463;636F6C6F7273"),
+            "\x1bP1+r5463\x1b\\\x1bP1+r636F6C6F7273=323536\x1b\\"
+        );
+        assert_eq!(request_termcap("787878"), "\x1bP0+r787878\x1b\\");
+    }
+}
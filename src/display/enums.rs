@@ -1,3 +1,9 @@
+use crate::{
+    layout::{LineAttribute, PromptMark},
+    notification::Notification,
+    terminal::Progress,
+};
+
 #[derive(Debug, PartialEq)]
 pub enum AnsiSequence {
     Character(char),
@@ -7,20 +13,78 @@ pub enum AnsiSequence {
     CursorDown(u16),
     CursorForward(u16),
     CursorBackward(u16),
-    SetGraphicsMode(Vec<u8>),
+    // SGR parameters, each with its `:` separated subparameters
+    SetGraphicsMode(Vec<Vec<u16>>),
     SetTitleMode,
+    SetWorkingDirectory(String),
+    SemanticPrompt(PromptMark),
+    Notify(Notification),
+    // `None` clears the progress
+    SetProgress(Option<Progress>),
+    SetUserVar(String, String),
+    // the arguments and base64 payload of an iTerm2 `OSC 1337;File=` image
+    InlineImage(String),
+    DeviceControl(Dcs),
+    // the control data and payload of a kitty graphics APC sequence
+    KittyGraphics(String),
+    // OSC 99 metadata and payload, which may be one chunk of a larger notification
+    KittyNotify(String, String),
     SetBracketedPasteMode(bool),
+    // a string sequence that was cancelled or isn't understood
+    IgnoredString,
     Bell,
     Back,
     ShowCursor,
+    // DECSCUSR, `CSI Ps SP q`
+    SetCursorStyle(u16),
     HideCursor,
     AutoWrap(bool),
+    // DECSET and DECRST, `CSI ? Pm h` and `CSI ? Pm l`
+    SetPrivateMode(Vec<u16>, bool),
+    // DECRQM, `CSI ? Ps $ p` for private modes and `CSI Ps $ p` for ANSI ones
+    RequestMode(u16, bool),
+    // the kitty keyboard protocol, pushing, popping, setting and querying the flags
+    PushKeyboardMode(u32),
+    PopKeyboardMode(u32),
+    // the flags and whether they replace (1), are added to (2) or removed from (3) the current ones
+    SetKeyboardMode(u32, u8),
+    QueryKeyboardMode,
+    // XTMODKEYS, `CSI > Pp ; Pv m`, where no value resets the resource, as does `CSI > Pp n`
+    SetKeyModifierOptions(u16, Option<u8>),
+    // XTQMODKEYS, `CSI ? Pp m`
+    QueryKeyModifierOptions(u16),
     EraseInLine(u8),
     EraseInDisplay(u8),
-    SetCharSet(CharSet),
+    // designates a character set to G0 or G1
+    SetCharSet(usize, CharSet),
+    // DECKPAM and DECKPNM, `ESC =` and `ESC >`
+    SetKeypadMode(bool),
+    // HTS, `ESC H`
+    SetTabStop,
+    // TBC, `CSI Ps g`
+    ClearTabStop(u16),
+    // RIS, `ESC c`
+    FullReset,
+    // DECSTR, `CSI ! p`
+    SoftReset,
+    // DECDWL, DECDHL and DECSWL, `ESC # 3` to `ESC # 6`
+    SetLineAttribute(LineAttribute),
+    // DECALN, `ESC # 8`
+    ScreenAlignment,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CharSet {
-    ASCII,
+    Ascii,
+    // the line drawing characters, `ESC ( 0`
+    DecSpecialGraphics,
+}
+
+// a device control string, `ESC P params intermediates final data ST`
+#[derive(Debug, PartialEq)]
+pub struct Dcs {
+    pub params: Vec<u16>,
+    pub intermediates: String,
+    pub final_char: char,
+    pub data: String,
 }
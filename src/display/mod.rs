@@ -1,22 +1,36 @@
-use crate::{config::ColorPallete, display::enums::CharSet, layout::AnsiChar, terminal::Terminal};
+use std::path::PathBuf;
+
+use crate::{
+    config::ColorPallete,
+    display::enums::CharSet,
+    graphics::{iterm, kitty, sixel, Image, Placement},
+    layout::{AnsiChar, Grid, LineAttribute},
+    notification,
+    terminal::{CursorStyle, Terminal},
+};
 
 use self::enums::AnsiSequence;
 
+mod dcs;
 mod enums;
 mod parsers;
 
 #[derive(Debug, PartialEq)]
-enum AnsiMode {
+pub enum AnsiMode {
     Print,
     Title,
 }
 
+// the pen and parser mode, kept on the terminal between reads
 #[derive(Debug)]
-struct Setting {
+pub struct Setting {
     pub color: [f32; 4],
     pub bg_color: [f32; 4],
     pub mode: AnsiMode,
     pub pallete: ColorPallete,
+    // the G0 and G1 character sets, shift out (SO) switches to G1 and shift in (SI) back
+    pub charsets: [CharSet; 2],
+    pub shifted: bool,
 }
 
 impl Default for Setting {
@@ -26,35 +40,135 @@ impl Default for Setting {
             bg_color: [0.0, 0.0, 0.0, 1.0],
             mode: AnsiMode::Print,
             pallete: ColorPallete::default(),
+            charsets: [CharSet::Ascii; 2],
+            shifted: false,
         }
     }
 }
 
 impl Setting {
-    pub fn set_graphics_mode(&mut self, code: Vec<u8>) {
-        match code.len() {
-            0 => self.color = self.pallete.white,
-            5 => {
-                if code[0] == 38 && code[1] == 2 {
-                    self.set_graphics_mode_rgb(code[2], code[3], code[4]);
-                    println!("test");
+    // maps a printed character through the active character set
+    pub fn translate(&self, c: char) -> char {
+        match self.charsets[usize::from(self.shifted)] {
+            CharSet::Ascii => c,
+            CharSet::DecSpecialGraphics => dec_special_graphics(c),
+        }
+    }
+
+    pub fn set_graphics_mode(&mut self, params: Vec<Vec<u16>>) {
+        if params.is_empty() {
+            self.set_graphics_mode_1(0);
+        }
+
+        let mut params = params.into_iter();
+        while let Some(param) = params.next() {
+            match param[0] {
+                // extended colors are either `38:5:n` and `38:2::r:g:b`, or spread over the
+                // following parameters as `38;5;n` and `38;2;r;g;b`
+                code @ (38 | 48 | 58) => {
+                    let args = if param.len() > 1 {
+                        param[1..].to_vec()
+                    } else {
+                        let kind = params.next().map_or(0, |param| param[0]);
+                        let count = match kind {
+                            5 => 1,
+                            2 => 3,
+                            _ => 0,
+                        };
+                        std::iter::once(kind)
+                            .chain(params.by_ref().take(count).map(|param| param[0]))
+                            .collect()
+                    };
+
+                    // underline colors are read so they aren't taken for other parameters
+                    match (code, self.extended_color(&args)) {
+                        (38, Some(color)) => self.color = color,
+                        (48, Some(color)) => self.bg_color = color,
+                        _ => {}
+                    }
                 }
+                code => self.set_graphics_mode_1(code),
             }
-            x => {
-                for i in 0..x {
-                    self.set_graphics_mode_1(code[i]);
-                }
+        }
+    }
+
+    // `5;n` picks from the 256 color palette, `2;r;g;b` and `2;colorspace;r;g;b` are rgb
+    fn extended_color(&self, args: &[u16]) -> Option<[f32; 4]> {
+        let rgb = |r: u16, g: u16, b: u16| {
+            let channel = |c: u16| c.min(255) as f32 / 255.0;
+            [channel(r), channel(g), channel(b), 1.0]
+        };
+
+        match *args {
+            [5, n, ..] => Some(self.indexed_color(n.min(255) as u8)),
+            [2, _, r, g, b, ..] | [2, r, g, b] => Some(rgb(r, g, b)),
+            _ => None,
+        }
+    }
+
+    // xterm's 256 colors: the palette, its bright versions, a 6x6x6 cube and a gray ramp
+    fn indexed_color(&self, n: u8) -> [f32; 4] {
+        let p = &self.pallete;
+        let colors = [
+            p.black, p.red, p.green, p.yellow, p.blue, p.magenta, p.cyan, p.white,
+        ];
+
+        match n {
+            0..=7 => colors[n as usize],
+            8 => [0.5, 0.5, 0.5, 1.0],
+            9..=15 => colors[n as usize - 8],
+            16..=231 => {
+                let level = |i: u8| {
+                    if i == 0 {
+                        0.0
+                    } else {
+                        (55 + 40 * i as u16) as f32 / 255.0
+                    }
+                };
+                let i = n - 16;
+                [level(i / 36), level(i / 6 % 6), level(i % 6), 1.0]
+            }
+            _ => {
+                let gray = (8 + 10 * (n - 232) as u16) as f32 / 255.0;
+                [gray, gray, gray, 1.0]
             }
         }
     }
 
-    fn set_graphics_mode_rgb(&mut self, r: u8, g: u8, b: u8) {
-        self.color = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0];
+    // the SGR parameters that would recreate the current pen, as reported by DECRQSS
+    pub fn sgr(&self) -> String {
+        let p = &self.pallete;
+        let colors = [
+            p.black, p.red, p.green, p.yellow, p.blue, p.magenta, p.cyan, p.white,
+        ];
+
+        let mut params = vec!["0".to_string()];
+
+        if self.color != p.white {
+            match colors.iter().position(|c| *c == self.color) {
+                Some(i) => params.push((30 + i).to_string()),
+                None => params.push(rgb_params(38, self.color)),
+            }
+        }
+
+        if self.bg_color != p.black {
+            match colors.iter().position(|c| *c == self.bg_color) {
+                Some(i) => params.push((40 + i).to_string()),
+                None => params.push(rgb_params(48, self.bg_color)),
+            }
+        }
+
+        params.join(";")
     }
 
-    fn set_graphics_mode_1(&mut self, code: u8) {
+    fn set_graphics_mode_1(&mut self, code: u16) {
         match code {
-            0 => self.color = self.pallete.white,
+            0 => {
+                self.color = self.pallete.white;
+                self.bg_color = self.pallete.black;
+            }
+            39 => self.color = self.pallete.white,
+            49 => self.bg_color = self.pallete.black,
             30 => self.color = self.pallete.black,
             31 => self.color = self.pallete.red,
             32 => self.color = self.pallete.green,
@@ -76,38 +190,338 @@ impl Setting {
     }
 }
 
-pub fn display_ansi_text(terminal: &mut Terminal, text: String) {
-    // TODO: take config input
-    let mut setting = Setting::default();
+fn rgb_params(code: u8, color: [f32; 4]) -> String {
+    format!(
+        "{};2;{};{};{}",
+        code,
+        (color[0] * 255.0).round() as u8,
+        (color[1] * 255.0).round() as u8,
+        (color[2] * 255.0).round() as u8
+    )
+}
+
+// moves the cursor down a row, scrolling the grid once it reaches the bottom
+fn linefeed(cursor: &mut (u32, u32), grid: &mut Grid) {
+    if cursor.1 + 1 < grid.size.1 {
+        cursor.1 += 1;
+    } else {
+        grid.scroll_up();
+    }
+}
+
+// string sequences longer than this are displayed as is rather than waiting for the rest
+const MAX_PENDING: usize = 64 * 1024 * 1024;
+
+// whether the rest of a string sequence has CAN, SUB or an ESC that doesn't start ST in it, which
+// all cancel the string. an ESC at the very end may still become ST
+fn cancelled(rest: &str) -> bool {
+    rest.contains(['\x18', '\x1a'])
+        || rest
+            .match_indices('\x1b')
+            .any(|(i, _)| rest.as_bytes().get(i + 1).is_some_and(|&c| c != b'\\'))
+}
+
+// where an OSC, DCS or APC sequence starts that isn't terminated or cancelled within `text`
+fn unterminated_start(text: &str) -> Option<usize> {
+    let start = ["\x1b]", "\x1bP", "\x1b_"]
+        .iter()
+        .filter_map(|s| text.rfind(s))
+        .max()?;
+
+    let rest = &text[start + 2..];
+    let terminated =
+        rest.contains("\x1b\\") || (text[start..].starts_with("\x1b]") && rest.contains('\x07'));
+
+    if terminated || cancelled(rest) || text.len() - start > MAX_PENDING {
+        None
+    } else {
+        Some(start)
+    }
+}
+
+// adds `text` to the unterminated sequence kept from earlier output and gives back all of it
+// once the sequence ends. only the new text is searched, so a large image sent in many chunks
+// isn't scanned again for every one of them
+fn continue_pending(pending: &mut String, text: &str) -> Option<String> {
+    // the ESC of an ST may have been the last thing in the previous chunk
+    let from = if pending.ends_with('\x1b') {
+        pending.len() - 1
+    } else {
+        pending.len()
+    };
+    pending.push_str(text);
+
+    let new = &pending[from..];
+    let terminated =
+        new.contains("\x1b\\") || (pending.starts_with("\x1b]") && new.contains('\x07'));
+
+    if terminated || cancelled(new) || pending.len() > MAX_PENDING {
+        Some(std::mem::take(pending))
+    } else {
+        None
+    }
+}
 
-    println!("{:?}", text);
+// adds an image to the grid at the cursor, moving the cursor below it. an empty image, like a
+// sixel that drew nothing, is dropped and leaves the cursor alone
+fn place_image(terminal: &mut Terminal, image: Image, width: f32, height: f32) {
+    if image.width == 0 || image.height == 0 {
+        return;
+    }
 
+    let (_, cell_height) = terminal.layout.cell_size();
     let grid = &mut terminal.visible_grid;
 
+    let image_id = terminal.images.add(image);
+    let line = grid.screen_line(terminal.cursor.1.min(grid.size.1 - 1));
+    // like xterm, the image is drawn over the text beneath it
+    let placement = Placement::new(image_id, line, terminal.cursor.0, width, height);
+    grid.placements.push(placement);
+
+    let rows = (height / cell_height).ceil().max(1.0) as u32;
+    for _ in 0..rows {
+        linefeed(&mut terminal.cursor, grid);
+    }
+}
+
+// shows an image from a kitty graphics command at the cursor
+fn place_kitty_image(terminal: &mut Terminal, command: &kitty::Command) -> Result<(), String> {
+    let image = terminal
+        .images
+        .get(command.id)
+        .ok_or_else(|| "ENOENT:no such image".to_string())?;
+
+    let [x, y, w, h] = command.source;
+    let w = if w == 0 {
+        image.width.saturating_sub(x)
+    } else {
+        w
+    };
+    let h = if h == 0 {
+        image.height.saturating_sub(y)
+    } else {
+        h
+    };
+    if w == 0 || h == 0 {
+        return Err("EINVAL:empty source rectangle".to_string());
+    }
+
+    let (cell_width, cell_height) = terminal.layout.cell_size();
+    let (width, height) = match (command.cols, command.rows) {
+        (0, 0) => (w as f32, h as f32),
+        (cols, 0) => {
+            let width = cols as f32 * cell_width;
+            (width, width * h as f32 / w as f32)
+        }
+        (0, rows) => {
+            let height = rows as f32 * cell_height;
+            (height * w as f32 / h as f32, height)
+        }
+        (cols, rows) => (cols as f32 * cell_width, rows as f32 * cell_height),
+    };
+
+    let grid = &mut terminal.visible_grid;
+
+    // placing with the same ids again moves the placement
+    if command.placement_id != 0 {
+        grid.placements
+            .retain(|p| p.image_id != command.id || p.placement_id != command.placement_id);
+    }
+
+    let line = grid.screen_line(terminal.cursor.1.min(grid.size.1 - 1));
+    let mut placement = Placement::new(command.id, line, terminal.cursor.0, width, height);
+    placement.placement_id = command.placement_id;
+    placement.offset = (command.cell_offset.0 as f32, command.cell_offset.1 as f32);
+    placement.z_index = command.z_index;
+    if command.source != [0; 4] {
+        placement.source = Some([x, y, w, h]);
+    }
+    grid.placements.push(placement);
+
+    if !command.no_cursor_move {
+        let cols = ((width + command.cell_offset.0 as f32) / cell_width).ceil() as u32;
+        let rows = ((height + command.cell_offset.1 as f32) / cell_height).ceil() as u32;
+
+        for _ in 1..rows.max(1) {
+            linefeed(&mut terminal.cursor, grid);
+        }
+        terminal.cursor.0 = (terminal.cursor.0 + cols).min(grid.size.0);
+    }
+
+    Ok(())
+}
+
+fn kitty_graphics(terminal: &mut Terminal, body: &str) {
+    let (control, payload) = body.split_once(';').unwrap_or((body, ""));
+
+    let (mut command, payload) = match terminal
+        .kitty_graphics
+        .receive(kitty::Command::parse(control), payload)
+    {
+        Some(received) => received,
+        None => return,
+    };
+
+    // without an id or number kitty neither replies nor remembers the id it picks
+    let silent = command.id == 0 && command.number == 0;
+    if command.id == 0 && command.number != 0 {
+        if let Some(&id) = terminal.kitty_graphics.numbers.get(&command.number) {
+            if command.action == 'p' || command.action == 'd' {
+                command.id = id;
+            }
+        }
+    }
+
+    let result = match command.action {
+        'q' => kitty::load(&command, &payload).map(|_| ()),
+        't' | 'T' => kitty::load(&command, &payload).and_then(|image| {
+            if command.id == 0 {
+                command.id = terminal.images.add_kept(image);
+                if command.number != 0 {
+                    terminal
+                        .kitty_graphics
+                        .numbers
+                        .insert(command.number, command.id);
+                }
+            } else {
+                terminal.images.insert(command.id, image);
+            }
+
+            if command.action == 'T' {
+                place_kitty_image(terminal, &command)
+            } else {
+                Ok(())
+            }
+        }),
+        'p' => place_kitty_image(terminal, &command),
+        'd' => {
+            let grid = &mut terminal.visible_grid;
+            let cursor = (
+                terminal.cursor.0,
+                grid.screen_line(terminal.cursor.1.min(grid.size.1 - 1)),
+            );
+            let top_line = grid.screen_line(0);
+
+            kitty::delete(
+                &command,
+                &mut grid.placements,
+                &mut terminal.images,
+                cursor,
+                top_line,
+                terminal.layout.cell_size(),
+            );
+            Ok(())
+        }
+        _ => Err("EINVAL:unsupported action".to_string()),
+    };
+
+    if silent {
+        return;
+    }
+
+    if let Some(reply) = kitty::reply(&command, &result) {
+        terminal.reply(reply);
+    }
+}
+
+// the VT100 line drawing set, which replaces the characters from '`' to '~'
+fn dec_special_graphics(c: char) -> char {
+    const GRAPHICS: [char; 31] = [
+        '◆', '▒', '␉', '␌', '␍', '␊', '°', '±', '␤', '␋', '┘', '┐', '┌', '└', '┼', '⎺', '⎻', '─',
+        '⎼', '⎽', '├', '┤', '┴', '┬', '│', '≤', '≥', 'π', '≠', '£', '·',
+    ];
+
+    match c {
+        '`'..='~' => GRAPHICS[c as usize - '`' as usize],
+        _ => c,
+    }
+}
+
+// turns an OSC 7 `file://host/path` report into a local path, ignoring reports from other hosts
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let (host, path) = rest.split_at(rest.find('/')?);
+
+    if !host.is_empty() && host != "localhost" {
+        let hostname = nix::unistd::gethostname().ok()?;
+        if hostname.to_str() != Some(host) {
+            return None;
+        }
+    }
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+pub fn display_ansi_text(terminal: &mut Terminal, text: String) {
+    let mut title = String::new();
+
+    let mut text = if terminal.pending.is_empty() {
+        text
+    } else {
+        match continue_pending(&mut terminal.pending, &text) {
+            Some(text) => text,
+            None => return,
+        }
+    };
+    if let Some(start) = unterminated_start(&text) {
+        terminal.pending = text.split_off(start);
+    }
+
     let (_input, ansichars) = parsers::parse(&text).unwrap();
 
     for c in ansichars {
-        println!("{:?}", c);
+        let grid = &mut terminal.visible_grid;
+        let setting = &mut terminal.setting;
+
         match c {
-            AnsiSequence::Character(c) if setting.mode == AnsiMode::Title => {}
+            AnsiSequence::Character(c) if setting.mode == AnsiMode::Title => title.push(c),
             AnsiSequence::Character(c) => match c {
                 '\n' => {
-                    terminal.cursor.1 += 1;
+                    linefeed(&mut terminal.cursor, grid);
                     terminal.cursor.0 = 0;
                 }
                 '\r' => {
                     terminal.cursor.0 = 0;
                 }
+                '\t' => {
+                    let last = grid.columns(terminal.cursor.1) - 1;
+                    terminal.cursor.0 = (terminal.cursor.0 + 1..last)
+                        .find(|&col| terminal.tab_stops.get(col as usize) == Some(&true))
+                        .unwrap_or(last);
+                }
+                '\u{e}' => setting.shifted = true,
+                '\u{f}' => setting.shifted = false,
                 _ => {
-                    if terminal.cursor.1 < grid.size.1 && terminal.cursor.0 < grid.size.0 {
-                        grid[terminal.cursor.1 as usize][terminal.cursor.0 as usize] =
-                            AnsiChar::new(c, setting.color, setting.bg_color);
-                        terminal.cursor.0 += 1;
-                    } else if terminal.cursor.1 < grid.size.0 {
-                        terminal.cursor.1 += 1;
-                        terminal.cursor.0 = 0;
+                    let columns = grid.columns(terminal.cursor.1);
+                    if terminal.cursor.0 >= columns {
+                        if terminal.modes.autowrap {
+                            linefeed(&mut terminal.cursor, grid);
+                            terminal.cursor.0 = 0;
+                        } else {
+                            terminal.cursor.0 = columns - 1;
+                        }
+                    }
+                    if terminal.cursor.1 < grid.size.1
+                        && terminal.cursor.0 < grid.columns(terminal.cursor.1)
+                    {
                         grid[terminal.cursor.1 as usize][terminal.cursor.0 as usize] =
-                            AnsiChar::new(c, setting.color, setting.bg_color);
+                            AnsiChar::new(setting.translate(c), setting.color, setting.bg_color);
                         terminal.cursor.0 += 1;
                     }
                 }
@@ -119,13 +533,69 @@ pub fn display_ansi_text(terminal: &mut Terminal, text: String) {
                 setting.mode = AnsiMode::Title;
             }
             AnsiSequence::Bell => {
+                if setting.mode == AnsiMode::Title {
+                    terminal.title = std::mem::take(&mut title);
+                }
                 setting.mode = AnsiMode::Print;
             }
-            AnsiSequence::Back => {
-                if terminal.cursor.0 > 0 {
-                    terminal.cursor.0 -= 1;
+            AnsiSequence::SetWorkingDirectory(url) => {
+                terminal.current_dir = file_url_to_path(&url);
+            }
+            AnsiSequence::Notify(notification) => {
+                terminal.notifications.push(notification);
+            }
+            AnsiSequence::SetProgress(progress) => {
+                terminal.progress = progress;
+            }
+            AnsiSequence::SetUserVar(name, value) => {
+                terminal.user_vars.insert(name, value);
+            }
+            AnsiSequence::DeviceControl(dcs)
+                if dcs.final_char == 'q' && dcs.intermediates.is_empty() =>
+            {
+                let image = sixel::decode(&dcs.params, &dcs.data);
+                let (width, height) = (image.width as f32, image.height as f32);
+                place_image(terminal, image, width, height);
+                terminal.prune_images();
+            }
+            AnsiSequence::InlineImage(body) => {
+                let file = match iterm::File::parse(&body) {
+                    Some(file) if file.inline => file,
+                    _ => continue,
+                };
+
+                if let Ok(image) = file.decode() {
+                    let (width, height) = file.size(
+                        (image.width, image.height),
+                        terminal.layout.cell_size(),
+                        (terminal.layout.px_width, terminal.layout.px_height),
+                    );
+                    place_image(terminal, image, width, height);
+                    terminal.prune_images();
+                }
+            }
+            AnsiSequence::KittyGraphics(body) => {
+                kitty_graphics(terminal, &body);
+            }
+            AnsiSequence::DeviceControl(dcs) => {
+                if let Some(reply) = dcs::respond(&dcs, terminal) {
+                    terminal.reply(reply);
+                }
+            }
+            AnsiSequence::KittyNotify(metadata, payload) => {
+                if let Some(notification) =
+                    notification::kitty_chunk(&mut terminal.kitty_notification, &metadata, &payload)
+                {
+                    terminal.notifications.push(notification);
                 }
             }
+            AnsiSequence::SemanticPrompt(mark) => {
+                let line = grid.screen_line(terminal.cursor.1.min(grid.size.1 - 1));
+                grid.mark(line, terminal.cursor.0, mark);
+            }
+            AnsiSequence::Back if terminal.cursor.0 > 0 => {
+                terminal.cursor.0 -= 1;
+            }
             AnsiSequence::CursorUp(n) => {
                 if terminal.cursor.1 >= n as u32 {
                     terminal.cursor.1 -= n as u32;
@@ -141,10 +611,11 @@ pub fn display_ansi_text(terminal: &mut Terminal, text: String) {
                 }
             }
             AnsiSequence::CursorForward(n) => {
-                if terminal.cursor.0 + (n as u32) < grid.size.0 {
+                let columns = grid.columns(terminal.cursor.1);
+                if terminal.cursor.0 + (n as u32) < columns {
                     terminal.cursor.0 += n as u32;
                 } else {
-                    terminal.cursor.0 = grid.size.0 - 1;
+                    terminal.cursor.0 = columns - 1;
                 }
             }
             AnsiSequence::CursorBackward(n) => {
@@ -168,28 +639,87 @@ pub fn display_ansi_text(terminal: &mut Terminal, text: String) {
                 }
             }
             AnsiSequence::ShowCursor => terminal.visible_cursor = true,
+            AnsiSequence::SetCursorStyle(ps) => {
+                if let Some(style) = CursorStyle::from_decscusr(ps, terminal.default_cursor_style) {
+                    terminal.cursor_style = style;
+                }
+            }
             AnsiSequence::HideCursor => terminal.visible_cursor = false,
-            AnsiSequence::AutoWrap(state) => {}
-            AnsiSequence::EraseInLine(n) => match n {
-                0 => {
-                    for i in terminal.cursor.0..grid.size.0 {
-                        grid[terminal.cursor.1 as usize][i as usize] = AnsiChar::default();
-                    }
+            AnsiSequence::AutoWrap(state) => terminal.modes.autowrap = state,
+            AnsiSequence::RequestMode(mode, private) => {
+                // no ANSI modes are supported
+                let (prefix, state) = if private {
+                    ("?", terminal.private_mode_state(mode))
+                } else {
+                    ("", 0)
+                };
+                let reply = format!("\x1b[{}{};{}$y", prefix, mode, state);
+                terminal.reply(reply);
+            }
+            AnsiSequence::PushKeyboardMode(flags) => terminal.push_keyboard_mode(flags),
+            AnsiSequence::PopKeyboardMode(count) => terminal.pop_keyboard_mode(count),
+            AnsiSequence::SetKeyboardMode(flags, mode) => terminal.set_keyboard_mode(flags, mode),
+            AnsiSequence::QueryKeyboardMode => {
+                let reply = format!("\x1b[?{}u", terminal.keyboard_flags());
+                terminal.reply(reply);
+            }
+            // only modifyOtherKeys is supported
+            AnsiSequence::SetKeyModifierOptions(4, level) => {
+                terminal.modes.modify_other_keys = level.unwrap_or(0).min(2);
+            }
+            AnsiSequence::QueryKeyModifierOptions(4) => {
+                let reply = format!("\x1b[>4;{}m", terminal.modes.modify_other_keys);
+                terminal.reply(reply);
+            }
+            AnsiSequence::SetPrivateMode(modes, state) => {
+                for mode in modes {
+                    terminal.set_private_mode(mode, state);
                 }
-                _ => {}
-            },
-            AnsiSequence::EraseInDisplay(n) => match n {
-                0 => {
-                    for i in terminal.cursor.1..grid.size.1 {
-                        for j in terminal.cursor.0..grid.size.0 {
-                            grid[i as usize][j as usize] = AnsiChar::default();
-                        }
+            }
+            AnsiSequence::SetBracketedPasteMode(state) => terminal.modes.bracketed_paste = state,
+            AnsiSequence::EraseInLine(0) => {
+                for i in terminal.cursor.0..grid.size.0 {
+                    grid[terminal.cursor.1 as usize][i as usize] = AnsiChar::default();
+                }
+            }
+            AnsiSequence::EraseInDisplay(0) => {
+                for i in terminal.cursor.1..grid.size.1 {
+                    for j in terminal.cursor.0..grid.size.0 {
+                        grid[i as usize][j as usize] = AnsiChar::default();
                     }
                 }
-                _ => {}
-            },
-            AnsiSequence::SetCharSet(CharSet::ASCII) => {
-                // todo
+            }
+            AnsiSequence::SetCharSet(slot, charset) => setting.charsets[slot] = charset,
+            AnsiSequence::SetKeypadMode(application) => {
+                terminal.modes.application_keypad = application;
+            }
+            AnsiSequence::SetTabStop => {
+                if let Some(stop) = terminal.tab_stops.get_mut(terminal.cursor.0 as usize) {
+                    *stop = true;
+                }
+            }
+            AnsiSequence::ClearTabStop(0) => {
+                if let Some(stop) = terminal.tab_stops.get_mut(terminal.cursor.0 as usize) {
+                    *stop = false;
+                }
+            }
+            AnsiSequence::ClearTabStop(3) => terminal.tab_stops.fill(false),
+            AnsiSequence::FullReset => {
+                terminal.full_reset();
+                title.clear();
+            }
+            AnsiSequence::SoftReset => terminal.soft_reset(),
+            AnsiSequence::SetLineAttribute(attribute) => {
+                let row = terminal.cursor.1.min(grid.size.1 - 1);
+                grid[row as usize].attribute = attribute;
+                if attribute != LineAttribute::SingleWidth {
+                    terminal.cursor.0 = terminal.cursor.0.min(grid.columns(row) - 1);
+                }
+            }
+            AnsiSequence::ScreenAlignment => {
+                let pallete = &setting.pallete;
+                grid.fill(AnsiChar::new('E', pallete.white, pallete.black));
+                terminal.cursor = (0, 0);
             }
             _ => {}
         }
@@ -198,7 +728,12 @@ pub fn display_ansi_text(terminal: &mut Terminal, text: String) {
 
 #[cfg(test)]
 mod tests {
-    use crate::layout::{Layout, Row};
+    use crate::{
+        input::mouse::{MouseEncoding, MouseMode},
+        layout::{Layout, PromptMark, Row},
+        notification::Notification,
+        terminal::CursorShape,
+    };
 
     use super::*;
 
@@ -261,4 +796,549 @@ mod tests {
 
         assert_eq!(terminal.visible_grid[0], expected_row);
     }
+
+    #[test]
+    fn test_working_directory() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]7;file://localhost/tmp/with%20space\x07".to_string(),
+        );
+
+        assert_eq!(terminal.current_dir, Some(PathBuf::from("/tmp/with space")));
+
+        display_ansi_text(&mut terminal, "\x1b]0;vim\x07".to_string());
+
+        assert_eq!(
+            terminal.window_title("{title} - {cwd}", true),
+            "vim - /tmp/with space"
+        );
+    }
+
+    #[test]
+    fn test_working_directory_other_host() {
+        assert_eq!(file_url_to_path("file://not-this-machine/tmp"), None);
+        assert_eq!(file_url_to_path("file:///tmp"), Some(PathBuf::from("/tmp")));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "1\r\n2\r\n3\r\n4\r\n5\r\n6".to_string());
+
+        assert_eq!(terminal.cursor, (1, 4));
+        assert_eq!(terminal.visible_grid[0][0].character, '2');
+        assert_eq!(terminal.visible_grid[4][0].character, '6');
+        assert_eq!(terminal.visible_grid.line(0)[0].character, '1');
+    }
+
+    #[test]
+    fn test_semantic_prompt() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07ab\r\ncd\r\n\x1b]133;D;1\x07"
+                .to_string(),
+        );
+
+        assert_eq!(
+            terminal.visible_grid[0].metadata.marks,
+            vec![(0, PromptMark::PromptStart), (2, PromptMark::CommandStart)]
+        );
+        assert_eq!(terminal.visible_grid[0].metadata.exit_status, Some(1));
+        assert_eq!(terminal.last_command_output(), Some("ab\ncd".to_string()));
+
+        // the status still reaches a command line that scrolled off the screen
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]133;B\x07false\r\n1\r\n2\r\n3\r\n4\r\n5\r\n\x1b]133;D;2\x07".to_string(),
+        );
+        let grid = &terminal.visible_grid;
+        let command_line = *grid
+            .lines_with_mark(PromptMark::CommandStart)
+            .last()
+            .unwrap();
+        assert!(command_line < grid.screen_line(0));
+        assert_eq!(grid.line(command_line).metadata.exit_status, Some(2));
+    }
+
+    #[test]
+    fn test_notify() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b]777;notify;Build;done\x07".to_string());
+
+        assert_eq!(
+            terminal.notifications,
+            vec![Notification::new("Build".to_string(), "done".to_string())]
+        );
+        assert_eq!(terminal.cursor, (0, 0));
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b]9;4;1;42\x07".to_string());
+
+        assert_eq!(terminal.window_title("{title}", true), "[42%] Nyat");
+        assert_eq!(terminal.window_title("{title}", false), "Nyat");
+
+        display_ansi_text(&mut terminal, "\x1b]9;4;0\x07".to_string());
+
+        assert_eq!(terminal.progress, None);
+    }
+
+    #[test]
+    fn test_user_var_title() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]1337;SetUserVar=gitBranch=bWFpbg==\x07".to_string(),
+        );
+
+        assert_eq!(
+            terminal.window_title("{title} ({user.gitBranch}{user.unset})", false),
+            "Nyat (main)"
+        );
+
+        // titles and values are shown as they are, not expanded again
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]0;{user.gitBranch} {cwd\x07".to_string(),
+        );
+        assert_eq!(
+            terminal.window_title("{ {title} {user.gitBranch}}", false),
+            "{ {user.gitBranch} {cwd main}"
+        );
+    }
+
+    #[test]
+    fn test_sgr_report() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b[31;42m".to_string());
+
+        assert_eq!(terminal.setting.sgr(), "0;31;42");
+
+        display_ansi_text(&mut terminal, "\x1b[38;2;1;2;3m".to_string());
+
+        assert_eq!(terminal.setting.sgr(), "0;38;2;1;2;3;42");
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        // what programs send once XTGETTCAP reports truecolor and 256 colors
+        display_ansi_text(&mut terminal, "\x1b[0;38;2;1;2;3m".to_string());
+        assert_eq!(terminal.setting.sgr(), "0;38;2;1;2;3");
+
+        display_ansi_text(&mut terminal, "\x1b[48;2;4;5;6m".to_string());
+        assert_eq!(terminal.setting.sgr(), "0;38;2;1;2;3;48;2;4;5;6");
+
+        display_ansi_text(&mut terminal, "\x1b[38:2::7:8:9;48:5:1m".to_string());
+        assert_eq!(terminal.setting.sgr(), "0;38;2;7;8;9;41");
+
+        display_ansi_text(&mut terminal, "\x1b[38;5;208;49m".to_string());
+        assert_eq!(terminal.setting.sgr(), "0;38;2;255;135;0");
+
+        display_ansi_text(&mut terminal, "\x1b[38;5;244m".to_string());
+        assert_eq!(terminal.setting.sgr(), "0;38;2;128;128;128");
+
+        // underline styles and colors aren't drawn, but aren't printed either
+        display_ansi_text(
+            &mut terminal,
+            "\x1b[0m\x1b[4:3m\x1b[58:2::1:2:3m\x1b[58;5;1;32m".to_string(),
+        );
+        assert_eq!(terminal.setting.sgr(), "0;32");
+        assert_eq!(terminal.visible_grid[0][0].character, ' ');
+        assert_eq!(terminal.cursor, (0, 0));
+    }
+
+    #[test]
+    fn test_cursor_style() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+        terminal.default_cursor_style = CursorStyle::new(CursorShape::Underline, false);
+
+        display_ansi_text(&mut terminal, "\x1b[5 q".to_string());
+        assert_eq!(
+            terminal.cursor_style,
+            CursorStyle::new(CursorShape::Bar, true)
+        );
+        assert_eq!(terminal.cursor_style.decscusr(), 5);
+
+        display_ansi_text(&mut terminal, "\x1b[0 q".to_string());
+        assert_eq!(terminal.cursor_style, terminal.default_cursor_style);
+        assert_eq!(terminal.cursor_style.decscusr(), 4);
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 20 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "a\t".to_string());
+        assert_eq!(terminal.cursor.0, 8);
+
+        display_ansi_text(&mut terminal, "\x1b[3g\r\x1b[3C\x1bH\r\t".to_string());
+        assert_eq!(terminal.cursor.0, 3);
+    }
+
+    #[test]
+    fn test_full_reset() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 20 + 5) as f32));
+
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]0;vim\x07\x1b[31m\x1b(0q\x1b[?7l\x1b[3g".to_string(),
+        );
+        assert_eq!(terminal.visible_grid[0][0].character, '─');
+
+        display_ansi_text(&mut terminal, "\x1bcq".to_string());
+
+        assert_eq!(terminal.visible_grid[0][0].character, 'q');
+        assert_eq!(terminal.visible_grid[0][1].character, ' ');
+        assert_eq!(terminal.cursor, (1, 0));
+        assert_eq!(terminal.setting.sgr(), "0");
+        assert_eq!(terminal.title, "");
+        assert!(terminal.modes.autowrap);
+        assert!(terminal.tab_stops[8]);
+
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]1337;SetUserVar=program=dmlt\x07\x1b]7;file:///tmp\x07\x1bc".to_string(),
+        );
+        assert!(terminal.user_vars.is_empty());
+        assert_eq!(terminal.current_dir, None);
+    }
+
+    #[test]
+    fn test_soft_reset() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "ab\x1b[?25l\x1b[32m\x1b[!p".to_string());
+
+        assert!(terminal.visible_cursor);
+        assert!(terminal.modes.autowrap);
+        assert_eq!(terminal.setting.sgr(), "0");
+        assert_eq!(terminal.visible_grid[0][1].character, 'b');
+        assert_eq!(terminal.cursor, (2, 0));
+
+        // what `reset` sends, lines still wrap afterwards
+        display_ansi_text(&mut terminal, "\x1bc\x1b[!p".to_string());
+        assert!(terminal.modes.autowrap);
+        let width = terminal.visible_grid.size.0 as usize;
+        display_ansi_text(&mut terminal, "x".repeat(width + 1));
+        assert_eq!(terminal.visible_grid[1][0].character, 'x');
+
+        // the cursor saved by DECSET 1049 is forgotten
+        display_ansi_text(&mut terminal, "\x1b[?1049h\x1b[!p\x1b[?1049l".to_string());
+        assert_eq!(terminal.cursor, (0, 0));
+    }
+
+    #[test]
+    fn test_double_width() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 20 + 5) as f32));
+        let half = terminal.visible_grid.size.0 / 2;
+
+        display_ansi_text(&mut terminal, "\x1b#6".to_string());
+        display_ansi_text(&mut terminal, "x".repeat(half as usize + 1));
+
+        assert_eq!(
+            terminal.visible_grid[0].attribute,
+            LineAttribute::DoubleWidth
+        );
+        assert_eq!(terminal.visible_grid[0][half as usize].character, ' ');
+        assert_eq!(terminal.visible_grid[1][0].character, 'x');
+        assert_eq!(terminal.cursor, (1, 1));
+    }
+
+    #[test]
+    fn test_screen_alignment() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b#3ab\x1b#8".to_string());
+
+        let grid = &terminal.visible_grid;
+        assert_eq!(grid[0].attribute, LineAttribute::SingleWidth);
+        assert!((0..grid.size.1 as usize)
+            .all(|row| (0..grid.size.0 as usize).all(|col| grid[row][col].character == 'E')));
+        assert_eq!(terminal.cursor, (0, 0));
+    }
+
+    #[test]
+    fn test_mouse_modes() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b[?1002;1006h".to_string());
+        assert_eq!(terminal.modes.mouse, MouseMode::Drag);
+        assert_eq!(terminal.modes.mouse_encoding, MouseEncoding::Sgr);
+
+        display_ansi_text(&mut terminal, "\x1b[?1002;1015l".to_string());
+        assert_eq!(terminal.modes.mouse, MouseMode::Off);
+        assert_eq!(terminal.modes.mouse_encoding, MouseEncoding::Sgr);
+    }
+
+    #[test]
+    fn test_focus_reporting() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        // catch what would be sent to the pty
+        let (transmitter, reciever) = std::sync::mpsc::channel();
+        terminal.transmitter = transmitter;
+
+        display_ansi_text(&mut terminal, "\x1b[?1004h".to_string());
+        assert!(terminal.modes.focus_reporting);
+        terminal.focus_changed(true);
+        assert_eq!(reciever.try_recv().unwrap(), "\x1b[I");
+        terminal.focus_changed(false);
+        assert_eq!(reciever.try_recv().unwrap(), "\x1b[O");
+
+        display_ansi_text(&mut terminal, "\x1b[?1004l".to_string());
+        assert!(!terminal.modes.focus_reporting);
+        terminal.focus_changed(true);
+        terminal.focus_changed(false);
+        assert!(reciever.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_application_keys() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b[?1h\x1b=a".to_string());
+        assert!(terminal.modes.application_cursor);
+        assert!(terminal.modes.application_keypad);
+        assert_eq!(terminal.visible_grid[0][0].character, 'a');
+
+        display_ansi_text(&mut terminal, "\x1b[?1l\x1b>".to_string());
+        assert!(!terminal.modes.application_cursor);
+        assert!(!terminal.modes.application_keypad);
+    }
+
+    #[test]
+    fn test_keyboard_modes() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b[>1u\x1b[>3u".to_string());
+        assert_eq!(terminal.keyboard_flags(), 3);
+
+        display_ansi_text(&mut terminal, "\x1b[=8;2u".to_string());
+        assert_eq!(terminal.keyboard_flags(), 11);
+
+        // the alternate screen has its own stack
+        display_ansi_text(&mut terminal, "\x1b[?1049h".to_string());
+        assert_eq!(terminal.keyboard_flags(), 0);
+        display_ansi_text(&mut terminal, "\x1b[?1049l".to_string());
+        assert_eq!(terminal.keyboard_flags(), 11);
+
+        display_ansi_text(&mut terminal, "\x1b[<u".to_string());
+        assert_eq!(terminal.keyboard_flags(), 1);
+        display_ansi_text(&mut terminal, "\x1b[<5u".to_string());
+        assert_eq!(terminal.keyboard_flags(), 0);
+    }
+
+    #[test]
+    fn test_modify_other_keys() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "\x1b[>4;2ma".to_string());
+        assert_eq!(terminal.modes.modify_other_keys, 2);
+        assert_eq!(terminal.visible_grid[0][0].character, 'a');
+
+        display_ansi_text(&mut terminal, "\x1b[>4m".to_string());
+        assert_eq!(terminal.modes.modify_other_keys, 0);
+    }
+
+    #[test]
+    fn test_synchronized_output() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+        let timeout = std::time::Duration::from_secs(60);
+
+        assert_eq!(terminal.private_mode_state(2026), 2);
+        assert!(!terminal.rendering_suspended(timeout));
+
+        display_ansi_text(&mut terminal, "\x1b[?2026ha".to_string());
+        assert_eq!(terminal.private_mode_state(2026), 1);
+        assert!(terminal.rendering_suspended(timeout));
+        assert!(!terminal.rendering_suspended(std::time::Duration::ZERO));
+        assert_eq!(terminal.visible_grid[0][0].character, 'a');
+
+        // setting the mode again doesn't restart the timeout
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        display_ansi_text(&mut terminal, "\x1b[?2026h".to_string());
+        assert!(!terminal.rendering_suspended(std::time::Duration::from_millis(10)));
+
+        display_ansi_text(&mut terminal, "\x1b[?2026l".to_string());
+        assert!(!terminal.rendering_suspended(timeout));
+        assert_eq!(terminal.private_mode_state(12345), 0);
+    }
+
+    #[test]
+    fn test_alternate_screen() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "ab\x1b[?1049hc\n\n\n\n\n\n".to_string());
+        assert!(terminal.modes.alternate_screen);
+        assert_eq!(terminal.visible_grid[0][0].character, ' ');
+        assert_eq!(
+            terminal.visible_grid.total_lines(),
+            terminal.height as usize
+        );
+
+        display_ansi_text(&mut terminal, "\x1b[?1049l".to_string());
+        assert!(!terminal.modes.alternate_screen);
+        assert_eq!(terminal.visible_grid[0][1].character, 'b');
+        assert_eq!(terminal.cursor, (2, 0));
+
+        // a full reset goes back to the normal screen, which it then clears
+        display_ansi_text(&mut terminal, "\x1b[?1049hd\x1bc".to_string());
+        assert!(!terminal.modes.alternate_screen);
+        assert!(terminal.visible_grid.history);
+        assert_eq!(terminal.visible_grid[0][0].character, ' ');
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        display_ansi_text(&mut terminal, "ab\x1bP0;1q#1;2;100;0;0".to_string());
+
+        // nothing is placed until the string terminator arrives
+        assert!(terminal.visible_grid.placements.is_empty());
+        assert_eq!(terminal.cursor, (2, 0));
+
+        // the string terminator can be split between chunks too
+        display_ansi_text(&mut terminal, "#1~~".to_string());
+        display_ansi_text(&mut terminal, "-@@\x1b".to_string());
+        assert!(terminal.visible_grid.placements.is_empty());
+        display_ansi_text(&mut terminal, "\\".to_string());
+
+        let placement = &terminal.visible_grid.placements[0];
+        assert_eq!((placement.line, placement.col), (0, 2));
+        assert_eq!((placement.width, placement.height), (2.0, 7.0));
+        assert!(placement.z_index >= 0);
+        assert_eq!(terminal.cursor, (2, 1));
+
+        let image = terminal.images.get(placement.image_id).unwrap();
+        assert_eq!(&image.pixels[..4], &[255, 0, 0, 255]);
+
+        // a sixel that draws nothing isn't placed and doesn't move the cursor
+        display_ansi_text(&mut terminal, "\x1bP0;1q\"1;1;10;10\x1b\\".to_string());
+        assert_eq!(terminal.visible_grid.placements.len(), 1);
+        assert_eq!(terminal.cursor, (2, 1));
+    }
+
+    #[test]
+    fn test_cancelled_string() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        // a stray DCS in binary output doesn't swallow the `reset` typed after it
+        display_ansi_text(&mut terminal, "ab\x1bPq#1;2;100".to_string());
+        assert!(!terminal.pending.is_empty());
+        display_ansi_text(&mut terminal, "\x1bcx".to_string());
+        assert!(terminal.pending.is_empty());
+        assert_eq!(terminal.visible_grid[0][0].character, 'x');
+        assert_eq!(terminal.visible_grid[0][1].character, ' ');
+        assert_eq!(terminal.cursor, (1, 0));
+
+        // the cancelled string isn't printed
+        display_ansi_text(&mut terminal, "\x1bPq#1~~\x1bc\x1b_Gabc\x18y".to_string());
+        assert!(terminal.pending.is_empty());
+        assert_eq!(terminal.visible_grid[0][0].character, 'y');
+        assert_eq!(terminal.cursor, (1, 0));
+
+        display_ansi_text(
+            &mut terminal,
+            "\x1b]8;;x\x1az\x1b]52;c;eA==\x07".to_string(),
+        );
+        assert_eq!(terminal.visible_grid[0][1].character, 'z');
+        assert_eq!(terminal.cursor, (2, 0));
+    }
+
+    #[test]
+    fn test_kitty_graphics() {
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        // a red and a green pixel, sent in two chunks
+        display_ansi_text(
+            &mut terminal,
+            "\x1b_Ga=T,q=2,i=5,s=2,v=1,z=1,m=1;/wAA\x1b\\".to_string(),
+        );
+        assert!(terminal.visible_grid.placements.is_empty());
+
+        display_ansi_text(&mut terminal, "\x1b_Gm=0;/wD/AP8=\x1b\\".to_string());
+
+        let placement = &terminal.visible_grid.placements[0];
+        assert_eq!((placement.image_id, placement.z_index), (5, 1));
+        assert_eq!((placement.width, placement.height), (2.0, 1.0));
+        assert_eq!(terminal.cursor, (1, 0));
+
+        let image = terminal.images.get(5).unwrap();
+        assert_eq!(image.pixels, [255, 0, 0, 255, 0, 255, 0, 255]);
+
+        display_ansi_text(&mut terminal, "\x1b_Ga=d,d=I,i=5,q=2\x1b\\".to_string());
+        assert!(terminal.visible_grid.placements.is_empty());
+        assert!(terminal.images.get(5).is_none());
+    }
+
+    #[test]
+    fn test_inline_image() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let mut terminal =
+            Terminal::new(Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32));
+
+        let mut png = vec![];
+        image::RgbaImage::from_pixel(4, 2, image::Rgba([0, 0, 255, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+
+        display_ansi_text(
+            &mut terminal,
+            format!(
+                "\x1b]1337;File=inline=1;width=2:{}\x07",
+                STANDARD.encode(png)
+            ),
+        );
+
+        let placement = &terminal.visible_grid.placements[0];
+        let (cell_width, _) = terminal.layout.cell_size();
+        assert_eq!(placement.width, cell_width * 2.0);
+        assert_eq!(placement.height, cell_width);
+
+        let image = terminal.images.get(placement.image_id).unwrap();
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(&image.pixels[..4], &[0, 0, 255, 255]);
+    }
 }
@@ -1,18 +1,27 @@
 // inspired by https://gitlab.com/davidbittner/ansi-parser/
+use base64::{engine::general_purpose::STANDARD, Engine};
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::digit0;
-use nom::combinator::opt;
-use nom::multi::many0;
+use nom::bytes::complete::{tag, take_till, take_while};
+use nom::character::complete::{digit0, digit1};
+use nom::character::complete::{one_of, satisfy};
+use nom::combinator::{opt, peek};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::preceded;
 use nom::IResult;
 
-use super::enums::{AnsiSequence, CharSet};
+use crate::{
+    layout::{LineAttribute, PromptMark},
+    notification::Notification,
+    terminal::{Progress, ProgressState},
+};
+
+use super::enums::{AnsiSequence, CharSet, Dcs};
 
 type Res<'a> = IResult<&'a str, AnsiSequence>;
 
 macro_rules! tag_parser {
     ($sig:ident, $tag:expr, $ret:expr) => {
-        fn $sig(input: &str) -> Res {
+        fn $sig(input: &str) -> Res<'_> {
             let (input, _) = tag($tag)(input)?;
 
             Ok((input, $ret))
@@ -20,7 +29,7 @@ macro_rules! tag_parser {
     };
 }
 
-fn escape(input: &str) -> Res {
+fn escape(input: &str) -> Res<'_> {
     let (input, _) = tag("\u{1b}")(input)?;
 
     Ok((input, AnsiSequence::Escape))
@@ -33,7 +42,7 @@ fn parse_number_or_default(input: &str, default: u32) -> u32 {
     }
 }
 
-fn cursor_pos(input: &str) -> Res {
+fn cursor_pos(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, x) = digit0(input)?;
@@ -51,7 +60,7 @@ fn cursor_pos(input: &str) -> Res {
     Ok((input, AnsiSequence::CursorPos(x as u16, y as u16)))
 }
 
-fn cursor_up(input: &str) -> Res {
+fn cursor_up(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, n) = digit0(input)?;
@@ -63,7 +72,7 @@ fn cursor_up(input: &str) -> Res {
     Ok((input, AnsiSequence::CursorUp(n as u16)))
 }
 
-fn cursor_down(input: &str) -> Res {
+fn cursor_down(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, n) = digit0(input)?;
@@ -75,7 +84,7 @@ fn cursor_down(input: &str) -> Res {
     Ok((input, AnsiSequence::CursorDown(n as u16)))
 }
 
-fn cursor_forward(input: &str) -> Res {
+fn cursor_forward(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, n) = digit0(input)?;
@@ -87,7 +96,7 @@ fn cursor_forward(input: &str) -> Res {
     Ok((input, AnsiSequence::CursorForward(n as u16)))
 }
 
-fn cursor_backward(input: &str) -> Res {
+fn cursor_backward(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, n) = digit0(input)?;
@@ -99,30 +108,43 @@ fn cursor_backward(input: &str) -> Res {
     Ok((input, AnsiSequence::CursorBackward(n as u16)))
 }
 
-fn graphics_mode(input: &str) -> Res {
-    let mut v = vec![];
+fn cursor_style(input: &str) -> Res<'_> {
+    let (input, _) = tag("[")(input)?;
 
-    let (mut input, _) = tag("[")(input)?;
+    let (input, n) = digit0(input)?;
 
-    for _ in 1..=5 {
-        (input, _) = opt(tag(";"))(input)?;
+    let n = parse_number_or_default(n, 0);
 
-        let x = digit0(input)?;
-        input = x.0;
-        let n = x.1;
+    let (input, _) = tag(" q")(input)?;
 
-        match n.parse::<u8>() {
-            Ok(n) => v.push(n),
-            Err(_) => break,
-        }
-    }
+    Ok((input, AnsiSequence::SetCursorStyle(n as u16)))
+}
 
+// SGR, `CSI Pm m` where each parameter may have `:` separated subparameters, like the
+// `38:2::r:g:b` and `4:3` of truecolor and underline styles
+fn graphics_mode(input: &str) -> Res<'_> {
+    let (input, _) = tag("[")(input)?;
+    let (input, params) = take_while(|c: char| c.is_ascii_digit() || c == ';' || c == ':')(input)?;
     let (input, _) = tag("m")(input)?;
 
-    Ok((input, AnsiSequence::SetGraphicsMode(v)))
+    let params = if params.is_empty() {
+        vec![]
+    } else {
+        params
+            .split(';')
+            .map(|param| {
+                param
+                    .split(':')
+                    .map(|n| parse_number_or_default(n, 0).min(u16::MAX as u32) as u16)
+                    .collect()
+            })
+            .collect()
+    };
+
+    Ok((input, AnsiSequence::SetGraphicsMode(params)))
 }
 
-fn erase_in_line(input: &str) -> Res {
+fn erase_in_line(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, n) = digit0(input)?;
@@ -141,7 +163,7 @@ fn erase_in_line(input: &str) -> Res {
     }
 }
 
-fn erase_in_display(input: &str) -> Res {
+fn erase_in_display(input: &str) -> Res<'_> {
     let (input, _) = tag("[")(input)?;
 
     let (input, n) = digit0(input)?;
@@ -160,6 +182,199 @@ fn erase_in_display(input: &str) -> Res {
     }
 }
 
+// the body of an OSC sequence, terminated by either BEL or ST
+fn osc_string(input: &str) -> IResult<&str, &str> {
+    let (input, s) = take_till(|c| c == '\u{7}' || c == '\u{1b}')(input)?;
+
+    let (input, _) = alt((tag("\u{7}"), tag("\u{1b}\\")))(input)?;
+
+    Ok((input, s))
+}
+
+fn working_directory(input: &str) -> Res<'_> {
+    let (input, _) = tag("]7;")(input)?;
+
+    let (input, url) = osc_string(input)?;
+
+    Ok((input, AnsiSequence::SetWorkingDirectory(url.to_string())))
+}
+
+fn semantic_prompt(input: &str) -> Res<'_> {
+    let (input, _) = tag("]133;")(input)?;
+
+    let (input, body) = osc_string(input)?;
+
+    let mut params = body.split(';');
+
+    let mark = match params.next() {
+        Some("A") => PromptMark::PromptStart,
+        Some("B") => PromptMark::CommandStart,
+        Some("C") => PromptMark::OutputStart,
+        Some("D") => PromptMark::CommandEnd(params.next().and_then(|s| s.parse().ok())),
+        _ => {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Tag,
+            )))
+        }
+    };
+
+    Ok((input, AnsiSequence::SemanticPrompt(mark)))
+}
+
+fn progress(input: &str) -> Res<'_> {
+    let (input, _) = tag("]9;4;")(input)?;
+
+    let (input, body) = osc_string(input)?;
+
+    let mut params = body.split(';');
+
+    let state = params.next().unwrap_or("0");
+    let percent = params
+        .next()
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(0)
+        .min(100) as u8;
+
+    let state = match state {
+        "1" => ProgressState::Normal,
+        "2" => ProgressState::Error,
+        "3" => ProgressState::Indeterminate,
+        "4" => ProgressState::Paused,
+        _ => return Ok((input, AnsiSequence::SetProgress(None))),
+    };
+
+    Ok((
+        input,
+        AnsiSequence::SetProgress(Some(Progress { state, percent })),
+    ))
+}
+
+// iTerm2's `OSC 1337;SetUserVar=name=base64`
+fn set_user_var(input: &str) -> Res<'_> {
+    let (input, _) = tag("]1337;SetUserVar=")(input)?;
+
+    let (rest, body) = osc_string(input)?;
+
+    let value = body
+        .split_once('=')
+        .and_then(|(name, value)| Some((name, STANDARD.decode(value).ok()?)))
+        .and_then(|(name, value)| Some((name, String::from_utf8(value).ok()?)));
+
+    match value {
+        Some((name, value)) => Ok((rest, AnsiSequence::SetUserVar(name.to_string(), value))),
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        ))),
+    }
+}
+
+// iTerm2's `OSC 1337;File=args:base64`
+fn inline_image(input: &str) -> Res<'_> {
+    let (input, _) = tag("]1337;File=")(input)?;
+
+    let (input, body) = osc_string(input)?;
+
+    Ok((input, AnsiSequence::InlineImage(body.to_string())))
+}
+
+fn notify_osc9(input: &str) -> Res<'_> {
+    let (input, _) = tag("]9;")(input)?;
+
+    let (input, body) = osc_string(input)?;
+
+    Ok((
+        input,
+        AnsiSequence::Notify(Notification::new(String::new(), body.to_string())),
+    ))
+}
+
+fn notify_osc777(input: &str) -> Res<'_> {
+    let (input, _) = tag("]777;notify;")(input)?;
+
+    let (input, body) = osc_string(input)?;
+
+    let (title, body) = body.split_once(';').unwrap_or((body, ""));
+
+    Ok((
+        input,
+        AnsiSequence::Notify(Notification::new(title.to_string(), body.to_string())),
+    ))
+}
+
+fn notify_osc99(input: &str) -> Res<'_> {
+    let (input, _) = tag("]99;")(input)?;
+
+    let (input, body) = osc_string(input)?;
+
+    let (metadata, payload) = body.split_once(';').unwrap_or((body, ""));
+
+    Ok((
+        input,
+        AnsiSequence::KittyNotify(metadata.to_string(), payload.to_string()),
+    ))
+}
+
+fn device_control(input: &str) -> Res<'_> {
+    let (input, _) = tag("P")(input)?;
+
+    let (input, params) = take_while(|c: char| c.is_ascii_digit() || c == ';')(input)?;
+
+    let (input, intermediates) = take_while(|c: char| (' '..='/').contains(&c))(input)?;
+
+    let (input, final_char) = satisfy(|c| ('@'..='~').contains(&c))(input)?;
+
+    let (input, data) = take_till(|c| c == '\u{1b}')(input)?;
+
+    let (input, _) = tag("\u{1b}\\")(input)?;
+
+    Ok((
+        input,
+        AnsiSequence::DeviceControl(Dcs {
+            params: params
+                .split(';')
+                .filter(|s| !s.is_empty())
+                .map(|s| parse_number_or_default(s, 0) as u16)
+                .collect(),
+            intermediates: intermediates.to_string(),
+            final_char,
+            data: data.to_string(),
+        }),
+    ))
+}
+
+fn kitty_graphics(input: &str) -> Res<'_> {
+    let (input, _) = tag("_G")(input)?;
+
+    let (input, body) = take_till(|c| c == '\u{1b}')(input)?;
+
+    let (input, _) = tag("\u{1b}\\")(input)?;
+
+    Ok((input, AnsiSequence::KittyGraphics(body.to_string())))
+}
+
+// an OSC, DCS or APC string that CAN, SUB or another escape sequence cancelled, or that isn't
+// one we understand. either way it's dropped rather than printed, the escape sequence that
+// cancelled it is left to be parsed
+fn ignored_string(input: &str) -> Res<'_> {
+    let (input, kind) = one_of("]P_")(input)?;
+
+    let (input, _) = take_till(|c| {
+        matches!(c, '\u{18}' | '\u{1a}' | '\u{1b}') || (kind == ']' && c == '\u{7}')
+    })(input)?;
+
+    let (input, _) = alt((
+        tag("\u{7}"),
+        tag("\u{18}"),
+        tag("\u{1a}"),
+        tag("\u{1b}\\"),
+        peek(tag("\u{1b}")),
+    ))(input)?;
+
+    Ok((input, AnsiSequence::IgnoredString))
+}
+
 tag_parser!(set_title_mode, "]0;", AnsiSequence::SetTitleMode);
 tag_parser!(
     set_bracketed_paste_mode_on,
@@ -175,9 +390,190 @@ tag_parser!(show_cursor, "[?25h", AnsiSequence::ShowCursor);
 tag_parser!(hide_cursor, "[?25l", AnsiSequence::HideCursor);
 tag_parser!(autowrap_on, "[?7h", AnsiSequence::AutoWrap(true));
 tag_parser!(autowrap_off, "[?7l", AnsiSequence::AutoWrap(false));
-tag_parser!(set_char_set, "(B", AnsiSequence::SetCharSet(CharSet::ASCII));
+tag_parser!(set_tab_stop, "H", AnsiSequence::SetTabStop);
+tag_parser!(keypad_application, "=", AnsiSequence::SetKeypadMode(true));
+tag_parser!(keypad_numeric, ">", AnsiSequence::SetKeypadMode(false));
+tag_parser!(full_reset, "c", AnsiSequence::FullReset);
+tag_parser!(soft_reset, "[!p", AnsiSequence::SoftReset);
+
+fn line_attribute(input: &str) -> Res<'_> {
+    let (input, _) = tag("#")(input)?;
+
+    let (input, n) = satisfy(|c| matches!(c, '3'..='6' | '8'))(input)?;
+
+    let attribute = match n {
+        '3' => LineAttribute::DoubleHeightTop,
+        '4' => LineAttribute::DoubleHeightBottom,
+        '5' => LineAttribute::SingleWidth,
+        '6' => LineAttribute::DoubleWidth,
+        _ => return Ok((input, AnsiSequence::ScreenAlignment)),
+    };
+
+    Ok((input, AnsiSequence::SetLineAttribute(attribute)))
+}
+
+fn set_char_set(input: &str) -> Res<'_> {
+    let (input, slot) = alt((tag("("), tag(")")))(input)?;
 
-fn combined(input: &str) -> Res {
+    let (input, charset) = alt((tag("B"), tag("0")))(input)?;
+
+    let charset = match charset {
+        "0" => CharSet::DecSpecialGraphics,
+        _ => CharSet::Ascii,
+    };
+
+    Ok((
+        input,
+        AnsiSequence::SetCharSet(usize::from(slot == ")"), charset),
+    ))
+}
+
+fn clear_tab_stop(input: &str) -> Res<'_> {
+    let (input, _) = tag("[")(input)?;
+
+    let (input, n) = digit0(input)?;
+
+    let n = parse_number_or_default(n, 0);
+
+    let (input, _) = tag("g")(input)?;
+
+    Ok((input, AnsiSequence::ClearTabStop(n as u16)))
+}
+
+// resets, tab stops and character sets
+fn control(input: &str) -> Res<'_> {
+    alt((
+        full_reset,
+        soft_reset,
+        set_tab_stop,
+        clear_tab_stop,
+        keypad_application,
+        keypad_numeric,
+        set_char_set,
+        line_attribute,
+    ))(input)
+}
+
+fn private_mode(input: &str) -> Res<'_> {
+    let (input, _) = tag("[?")(input)?;
+
+    let (input, modes) = separated_list1(tag(";"), digit1)(input)?;
+
+    let (input, set) = alt((tag("h"), tag("l")))(input)?;
+
+    let modes = modes
+        .iter()
+        .map(|mode| parse_number_or_default(mode, 0) as u16)
+        .collect();
+
+    Ok((input, AnsiSequence::SetPrivateMode(modes, set == "h")))
+}
+
+fn request_mode(input: &str) -> Res<'_> {
+    let (input, _) = tag("[")(input)?;
+
+    let (input, private) = opt(tag("?"))(input)?;
+
+    let (input, mode) = digit1(input)?;
+
+    let (input, _) = tag("$p")(input)?;
+
+    Ok((
+        input,
+        AnsiSequence::RequestMode(parse_number_or_default(mode, 0) as u16, private.is_some()),
+    ))
+}
+
+// the kitty keyboard protocol, `CSI > flags u`, `CSI < count u`, `CSI = flags ; mode u` and
+// `CSI ? u`
+fn keyboard_mode(input: &str) -> Res<'_> {
+    let (input, _) = tag("[")(input)?;
+
+    let (input, prefix) = one_of("<>=?")(input)?;
+
+    let (input, first) = digit0(input)?;
+
+    let (input, second) = opt(preceded(tag(";"), digit1))(input)?;
+
+    let (input, _) = tag("u")(input)?;
+
+    let sequence = match prefix {
+        '>' => AnsiSequence::PushKeyboardMode(parse_number_or_default(first, 0)),
+        '<' => AnsiSequence::PopKeyboardMode(parse_number_or_default(first, 1)),
+        '=' => AnsiSequence::SetKeyboardMode(
+            parse_number_or_default(first, 0),
+            second.map_or(1, |mode| parse_number_or_default(mode, 1)) as u8,
+        ),
+        _ => AnsiSequence::QueryKeyboardMode,
+    };
+
+    Ok((input, sequence))
+}
+
+fn key_modifier_options(input: &str) -> Res<'_> {
+    let (input, _) = tag("[>")(input)?;
+
+    let (input, resource) = digit1(input)?;
+
+    let (input, value) = opt(preceded(tag(";"), digit1))(input)?;
+
+    let (input, last) = one_of("mn")(input)?;
+
+    let value = value
+        .filter(|_| last == 'm')
+        .map(|value| parse_number_or_default(value, 0) as u8);
+
+    Ok((
+        input,
+        AnsiSequence::SetKeyModifierOptions(parse_number_or_default(resource, 0) as u16, value),
+    ))
+}
+
+fn query_key_modifier_options(input: &str) -> Res<'_> {
+    let (input, _) = tag("[?")(input)?;
+
+    let (input, resource) = digit1(input)?;
+
+    let (input, _) = tag("m")(input)?;
+
+    Ok((
+        input,
+        AnsiSequence::QueryKeyModifierOptions(parse_number_or_default(resource, 0) as u16),
+    ))
+}
+
+// modes set with SM/RM and DECSET/DECRST
+fn modes(input: &str) -> Res<'_> {
+    alt((
+        set_bracketed_paste_mode_on,
+        set_bracketed_paste_mode_off,
+        show_cursor,
+        hide_cursor,
+        autowrap_on,
+        autowrap_off,
+        private_mode,
+        request_mode,
+        keyboard_mode,
+        key_modifier_options,
+        query_key_modifier_options,
+    ))(input)
+}
+
+fn osc(input: &str) -> Res<'_> {
+    alt((
+        set_title_mode,
+        working_directory,
+        semantic_prompt,
+        progress,
+        set_user_var,
+        inline_image,
+        notify_osc9,
+        notify_osc777,
+        notify_osc99,
+    ))(input)
+}
+
+fn combined(input: &str) -> Res<'_> {
     alt((
         escape,
         cursor_pos,
@@ -185,27 +581,26 @@ fn combined(input: &str) -> Res {
         cursor_down,
         cursor_forward,
         cursor_backward,
+        cursor_style,
         graphics_mode,
-        set_title_mode,
-        set_bracketed_paste_mode_on,
-        set_bracketed_paste_mode_off,
-        show_cursor,
-        hide_cursor,
-        autowrap_on,
-        autowrap_off,
+        osc,
+        device_control,
+        kitty_graphics,
+        ignored_string,
+        modes,
         erase_in_line,
         erase_in_display,
-        set_char_set,
+        control,
     ))(input)
 }
 
-fn parse_escape(input: &str) -> Res {
+fn parse_escape(input: &str) -> Res<'_> {
     let (input, _) = tag("\u{1b}")(input)?;
 
     combined(input)
 }
 
-fn parse_char(input: &str) -> Res {
+fn parse_char(input: &str) -> Res<'_> {
     let (input, c) = nom::character::complete::anychar(input)?;
 
     Ok((input, AnsiSequence::Character(c)))
@@ -268,31 +663,38 @@ mod tests {
         );
         assert_eq!(
             graphics_mode("[1m"),
-            Ok(("", AnsiSequence::SetGraphicsMode(vec![1])))
+            Ok(("", AnsiSequence::SetGraphicsMode(vec![vec![1]])))
         );
         assert_eq!(
             graphics_mode("[1;2m"),
-            Ok(("", AnsiSequence::SetGraphicsMode(vec![1, 2])))
-        );
-        assert_eq!(
-            graphics_mode("[1;2;3m"),
-            Ok(("", AnsiSequence::SetGraphicsMode(vec![1, 2, 3])))
+            Ok(("", AnsiSequence::SetGraphicsMode(vec![vec![1], vec![2]])))
         );
         assert_eq!(
-            graphics_mode("[1;2;3;4m"),
-            Ok(("", AnsiSequence::SetGraphicsMode(vec![1, 2, 3, 4])))
+            graphics_mode("[;2m"),
+            Ok(("", AnsiSequence::SetGraphicsMode(vec![vec![0], vec![2]])))
         );
         assert_eq!(
-            graphics_mode("[1;2;3;4;5m"),
-            Ok(("", AnsiSequence::SetGraphicsMode(vec![1, 2, 3, 4, 5])))
+            graphics_mode("[0;38;2;1;2;3m"),
+            Ok((
+                "",
+                AnsiSequence::SetGraphicsMode(vec![
+                    vec![0],
+                    vec![38],
+                    vec![2],
+                    vec![1],
+                    vec![2],
+                    vec![3]
+                ])
+            ))
         );
         assert_eq!(
-            graphics_mode("[1;2;3;4;5;6m"),
-            Err(nom::Err::Error(nom::error::Error::new(
-                ";6m",
-                nom::error::ErrorKind::Tag
-            )))
+            graphics_mode("[4:3;58:2::1:2:3m"),
+            Ok((
+                "",
+                AnsiSequence::SetGraphicsMode(vec![vec![4, 3], vec![58, 2, 0, 1, 2, 3]])
+            ))
         );
+        assert!(graphics_mode("[>4m").is_err());
     }
 
     #[test]
@@ -300,6 +702,265 @@ mod tests {
         assert_eq!(set_title_mode("]0;"), Ok(("", AnsiSequence::SetTitleMode)));
     }
 
+    #[test]
+    fn test_working_directory() {
+        assert_eq!(
+            working_directory("]7;file://host/home/user\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::SetWorkingDirectory("file://host/home/user".to_string())
+            ))
+        );
+        assert_eq!(
+            working_directory("]7;file:///tmp\u{1b}\\"),
+            Ok((
+                "",
+                AnsiSequence::SetWorkingDirectory("file:///tmp".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_semantic_prompt() {
+        assert_eq!(
+            semantic_prompt("]133;A\u{7}"),
+            Ok(("", AnsiSequence::SemanticPrompt(PromptMark::PromptStart)))
+        );
+        assert_eq!(
+            semantic_prompt("]133;C\u{1b}\\"),
+            Ok(("", AnsiSequence::SemanticPrompt(PromptMark::OutputStart)))
+        );
+        assert_eq!(
+            semantic_prompt("]133;D;127\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::SemanticPrompt(PromptMark::CommandEnd(Some(127)))
+            ))
+        );
+        assert_eq!(
+            semantic_prompt("]133;D\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::SemanticPrompt(PromptMark::CommandEnd(None))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_notify() {
+        assert_eq!(
+            notify_osc9("]9;done\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::Notify(Notification::new(String::new(), "done".to_string()))
+            ))
+        );
+        assert_eq!(
+            notify_osc777("]777;notify;Build;done\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::Notify(Notification::new("Build".to_string(), "done".to_string()))
+            ))
+        );
+        assert_eq!(
+            notify_osc99("]99;i=1:d=0;Build\u{1b}\\"),
+            Ok((
+                "",
+                AnsiSequence::KittyNotify("i=1:d=0".to_string(), "Build".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(
+            progress("]9;4;1;42\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::SetProgress(Some(Progress {
+                    state: ProgressState::Normal,
+                    percent: 42
+                }))
+            ))
+        );
+        assert_eq!(
+            progress("]9;4;3\u{1b}\\"),
+            Ok((
+                "",
+                AnsiSequence::SetProgress(Some(Progress {
+                    state: ProgressState::Indeterminate,
+                    percent: 0
+                }))
+            ))
+        );
+        assert_eq!(
+            progress("]9;4;2;300\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::SetProgress(Some(Progress {
+                    state: ProgressState::Error,
+                    percent: 100
+                }))
+            ))
+        );
+        assert_eq!(
+            progress("]9;4;0\u{7}"),
+            Ok(("", AnsiSequence::SetProgress(None)))
+        );
+    }
+
+    #[test]
+    fn test_set_user_var() {
+        assert_eq!(
+            set_user_var("]1337;SetUserVar=gitBranch=bWFpbg==\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::SetUserVar("gitBranch".to_string(), "main".to_string())
+            ))
+        );
+        assert!(set_user_var("]1337;SetUserVar=gitBranch=!!\u{7}").is_err());
+    }
+
+    #[test]
+    fn test_device_control() {
+        assert_eq!(
+            device_control("P$qm\u{1b}\\"),
+            Ok((
+                "",
+                AnsiSequence::DeviceControl(Dcs {
+                    params: vec![],
+                    intermediates: "$".to_string(),
+                    final_char: 'q',
+                    data: "m".to_string(),
+                })
+            ))
+        );
+        assert_eq!(
+            device_control("P1;2+q5463\u{1b}\\"),
+            Ok((
+                "",
+                AnsiSequence::DeviceControl(Dcs {
+                    params: vec![1, 2],
+                    intermediates: "+".to_string(),
+                    final_char: 'q',
+                    data: "5463".to_string(),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_control() {
+        assert_eq!(control("c"), Ok(("", AnsiSequence::FullReset)));
+        assert_eq!(control("[!p"), Ok(("", AnsiSequence::SoftReset)));
+        assert_eq!(control("[3g"), Ok(("", AnsiSequence::ClearTabStop(3))));
+        assert_eq!(control("="), Ok(("", AnsiSequence::SetKeypadMode(true))));
+        assert_eq!(control(">"), Ok(("", AnsiSequence::SetKeypadMode(false))));
+        assert_eq!(
+            control(")0"),
+            Ok(("", AnsiSequence::SetCharSet(1, CharSet::DecSpecialGraphics)))
+        );
+    }
+
+    #[test]
+    fn test_line_attribute() {
+        assert_eq!(
+            line_attribute("#3"),
+            Ok((
+                "",
+                AnsiSequence::SetLineAttribute(LineAttribute::DoubleHeightTop)
+            ))
+        );
+        assert_eq!(
+            line_attribute("#8"),
+            Ok(("", AnsiSequence::ScreenAlignment))
+        );
+        assert!(line_attribute("#7").is_err());
+    }
+
+    #[test]
+    fn test_private_mode() {
+        assert_eq!(
+            modes("[?1000;1006h"),
+            Ok(("", AnsiSequence::SetPrivateMode(vec![1000, 1006], true)))
+        );
+        assert_eq!(modes("[?25l"), Ok(("", AnsiSequence::HideCursor)));
+    }
+
+    #[test]
+    fn test_request_mode() {
+        assert_eq!(
+            modes("[?2026$p"),
+            Ok(("", AnsiSequence::RequestMode(2026, true)))
+        );
+        assert_eq!(modes("[4$p"), Ok(("", AnsiSequence::RequestMode(4, false))));
+    }
+
+    #[test]
+    fn test_keyboard_mode() {
+        assert_eq!(modes("[>11u"), Ok(("", AnsiSequence::PushKeyboardMode(11))));
+        assert_eq!(modes("[<u"), Ok(("", AnsiSequence::PopKeyboardMode(1))));
+        assert_eq!(
+            modes("[=5;2u"),
+            Ok(("", AnsiSequence::SetKeyboardMode(5, 2)))
+        );
+        assert_eq!(modes("[?u"), Ok(("", AnsiSequence::QueryKeyboardMode)));
+    }
+
+    #[test]
+    fn test_key_modifier_options() {
+        assert_eq!(
+            modes("[>4;2m"),
+            Ok(("", AnsiSequence::SetKeyModifierOptions(4, Some(2))))
+        );
+        assert_eq!(
+            modes("[>4m"),
+            Ok(("", AnsiSequence::SetKeyModifierOptions(4, None)))
+        );
+        assert_eq!(
+            modes("[>4n"),
+            Ok(("", AnsiSequence::SetKeyModifierOptions(4, None)))
+        );
+        assert_eq!(
+            modes("[?4m"),
+            Ok(("", AnsiSequence::QueryKeyModifierOptions(4)))
+        );
+    }
+
+    #[test]
+    fn test_cursor_style() {
+        assert_eq!(
+            cursor_style("[5 q"),
+            Ok(("", AnsiSequence::SetCursorStyle(5)))
+        );
+        assert_eq!(
+            cursor_style("[ q"),
+            Ok(("", AnsiSequence::SetCursorStyle(0)))
+        );
+    }
+
+    #[test]
+    fn test_inline_image() {
+        assert_eq!(
+            inline_image("]1337;File=inline=1;width=2:AAAA\u{7}"),
+            Ok((
+                "",
+                AnsiSequence::InlineImage("inline=1;width=2:AAAA".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_kitty_graphics() {
+        assert_eq!(
+            kitty_graphics("_Ga=T,f=100;AAAA\u{1b}\\"),
+            Ok((
+                "",
+                AnsiSequence::KittyGraphics("a=T,f=100;AAAA".to_string())
+            ))
+        );
+    }
+
     #[test]
     fn test_bell() {
         assert_eq!(parse_bel("\u{7}"), Ok(("", AnsiSequence::Bell)));
@@ -339,7 +1000,7 @@ mod tests {
             Ok((
                 "",
                 vec![
-                    AnsiSequence::SetGraphicsMode(vec![1, 1]),
+                    AnsiSequence::SetGraphicsMode(vec![vec![1], vec![1]]),
                     AnsiSequence::Character('t')
                 ]
             ))
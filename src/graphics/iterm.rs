@@ -0,0 +1,165 @@
+// iTerm2 inline images, `OSC 1337 ; File = key=value;... : base64 ST`
+//
+// https://iterm2.com/documentation-images.html
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::Image;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimension {
+    Auto,
+    Cells(u32),
+    Pixels(u32),
+    // of the terminal's width or height
+    Percent(u32),
+}
+
+impl Dimension {
+    fn parse(value: &str) -> Self {
+        let number = |s: &str| s.parse::<u32>().ok();
+
+        if let Some(n) = value.strip_suffix("px").and_then(number) {
+            Dimension::Pixels(n)
+        } else if let Some(n) = value.strip_suffix('%').and_then(number) {
+            Dimension::Percent(n)
+        } else if let Some(n) = number(value) {
+            Dimension::Cells(n)
+        } else {
+            Dimension::Auto
+        }
+    }
+
+    // the size in pixels, `cell` is the size of a cell and `whole` of the terminal in the same
+    // direction
+    fn resolve(self, cell: f32, whole: f32) -> Option<f32> {
+        match self {
+            Dimension::Auto => None,
+            Dimension::Cells(n) => Some(n as f32 * cell),
+            Dimension::Pixels(n) => Some(n as f32),
+            Dimension::Percent(n) => Some(n.min(100) as f32 / 100.0 * whole),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    // files that aren't inline are downloads, which aren't supported
+    pub inline: bool,
+    pub width: Dimension,
+    pub height: Dimension,
+    pub preserve_aspect_ratio: bool,
+    pub data: Vec<u8>,
+}
+
+impl File {
+    // parses what follows `File=`, returning None when the payload isn't valid base64
+    pub fn parse(body: &str) -> Option<Self> {
+        let (args, payload) = body.split_once(':')?;
+
+        let mut file = File {
+            inline: false,
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            preserve_aspect_ratio: true,
+            data: STANDARD.decode(payload.trim()).ok()?,
+        };
+
+        for arg in args.split(';') {
+            match arg.split_once('=') {
+                Some(("inline", value)) => file.inline = value == "1",
+                Some(("width", value)) => file.width = Dimension::parse(value),
+                Some(("height", value)) => file.height = Dimension::parse(value),
+                Some(("preserveAspectRatio", value)) => file.preserve_aspect_ratio = value != "0",
+                _ => {}
+            }
+        }
+
+        Some(file)
+    }
+
+    // decodes the PNG, JPEG or GIF data, only the first frame of an animation is kept
+    pub fn decode(&self) -> Result<Image, String> {
+        let image = image::load_from_memory(&self.data)
+            .map_err(|e| e.to_string())?
+            .to_rgba8();
+
+        Ok(Image::new(image.width(), image.height(), image.into_raw()))
+    }
+
+    // the size in pixels to draw an `image_size` image at, given the cell size and the size of
+    // the terminal
+    pub fn size(
+        &self,
+        image_size: (u32, u32),
+        cell_size: (f32, f32),
+        terminal_size: (f32, f32),
+    ) -> (f32, f32) {
+        let (image_width, image_height) = (image_size.0 as f32, image_size.1 as f32);
+        if image_width == 0.0 || image_height == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let width = self.width.resolve(cell_size.0, terminal_size.0);
+        let height = self.height.resolve(cell_size.1, terminal_size.1);
+
+        match (width, height) {
+            (None, None) => (image_width, image_height),
+            (Some(width), None) if self.preserve_aspect_ratio => {
+                (width, width * image_height / image_width)
+            }
+            (None, Some(height)) if self.preserve_aspect_ratio => {
+                (height * image_width / image_height, height)
+            }
+            (Some(width), Some(height)) if self.preserve_aspect_ratio => {
+                // fits the image in the box without stretching it
+                let scale = (width / image_width).min(height / image_height);
+                (image_width * scale, image_height * scale)
+            }
+            (width, height) => (width.unwrap_or(image_width), height.unwrap_or(image_height)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let file = File::parse("name=YQ==;inline=1;width=10;height=50%;preserveAspectRatio=0:AAEC")
+            .unwrap();
+
+        assert!(file.inline);
+        assert_eq!(file.width, Dimension::Cells(10));
+        assert_eq!(file.height, Dimension::Percent(50));
+        assert!(!file.preserve_aspect_ratio);
+        assert_eq!(file.data, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_size() {
+        let mut file = File::parse("inline=1;width=100px:").unwrap();
+        assert_eq!(
+            file.size((200, 50), (10.0, 20.0), (800.0, 600.0)),
+            (100.0, 25.0)
+        );
+
+        file.height = Dimension::Cells(2);
+        assert_eq!(
+            file.size((200, 50), (10.0, 20.0), (800.0, 600.0)),
+            (100.0, 25.0)
+        );
+
+        file.preserve_aspect_ratio = false;
+        assert_eq!(
+            file.size((200, 50), (10.0, 20.0), (800.0, 600.0)),
+            (100.0, 40.0)
+        );
+
+        file.width = Dimension::Percent(50);
+        assert_eq!(
+            file.size((200, 50), (10.0, 20.0), (800.0, 600.0)),
+            (400.0, 40.0)
+        );
+    }
+}
@@ -0,0 +1,517 @@
+// the kitty graphics protocol, `ESC _ G control ; payload ESC \`
+//
+// https://sw.kovidgoyal.net/kitty/graphics-protocol/
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::{Image, ImageStore, Placement};
+
+// transmissions larger than this are refused
+const MAX_DATA: usize = 400 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    // `a`, one of t(ransmit), T(ransmit and put), p(ut), d(elete), q(uery)
+    pub action: char,
+    // `t`, one of d(irect), f(ile), t(emp file), s(hared memory)
+    pub medium: char,
+    // `f`, 24 for RGB, 32 for RGBA and 100 for PNG
+    pub format: u32,
+    // `o=z`, the data is zlib compressed
+    pub compressed: bool,
+    // `m=1`, more chunks follow
+    pub more: bool,
+    // `q`, 1 suppresses OK replies and 2 suppresses errors too
+    pub quiet: u32,
+    pub id: u32,
+    // `I`, an image number the terminal picks an id for
+    pub number: u32,
+    pub placement_id: u32,
+    // `s` and `v`, the size of RGB and RGBA data in pixels
+    pub width: u32,
+    pub height: u32,
+    // `S` and `O`, how much of a file to read and from where
+    pub size: usize,
+    pub offset: usize,
+    // `x`, `y`, `w` and `h`, the part of the image to show
+    pub source: [u32; 4],
+    // `c` and `r`, the number of cells to show the image in
+    pub cols: u32,
+    pub rows: u32,
+    // `X` and `Y`, pixel offset within the first cell
+    pub cell_offset: (u32, u32),
+    pub z_index: i32,
+    // `C=1`, leave the cursor where it is
+    pub no_cursor_move: bool,
+    // `d`, what to delete
+    pub delete: char,
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Self {
+            action: 't',
+            medium: 'd',
+            format: 32,
+            compressed: false,
+            more: false,
+            quiet: 0,
+            id: 0,
+            number: 0,
+            placement_id: 0,
+            width: 0,
+            height: 0,
+            size: 0,
+            offset: 0,
+            source: [0; 4],
+            cols: 0,
+            rows: 0,
+            cell_offset: (0, 0),
+            z_index: 0,
+            no_cursor_move: false,
+            delete: 'a',
+        }
+    }
+}
+
+impl Command {
+    // parses the `,` separated `key=value` control data
+    pub fn parse(control: &str) -> Self {
+        let mut command = Command::default();
+
+        for pair in control.split(',') {
+            let (key, value) = match pair.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let c = value.chars().next().unwrap_or('\0');
+            let n = value.parse::<u32>().unwrap_or(0);
+
+            match key {
+                "a" => command.action = c,
+                "t" => command.medium = c,
+                "f" => command.format = n,
+                "o" => command.compressed = c == 'z',
+                "m" => command.more = n == 1,
+                "q" => command.quiet = n,
+                "i" => command.id = n,
+                "I" => command.number = n,
+                "p" => command.placement_id = n,
+                "s" => command.width = n,
+                "v" => command.height = n,
+                "S" => command.size = n as usize,
+                "O" => command.offset = n as usize,
+                "x" => command.source[0] = n,
+                "y" => command.source[1] = n,
+                "w" => command.source[2] = n,
+                "h" => command.source[3] = n,
+                "c" => command.cols = n,
+                "r" => command.rows = n,
+                "X" => command.cell_offset.0 = n,
+                "Y" => command.cell_offset.1 = n,
+                "z" => command.z_index = value.parse().unwrap_or(0),
+                "C" => command.no_cursor_move = n == 1,
+                "d" => command.delete = c,
+                _ => {}
+            }
+        }
+
+        command
+    }
+}
+
+// what the protocol keeps between commands
+#[derive(Debug, Default)]
+pub struct State {
+    // a chunked transmission, where only the first chunk carries the full control data
+    pending: Option<(Command, String)>,
+    // image numbers to the ids picked for them
+    pub numbers: HashMap<u32, u32>,
+}
+
+impl State {
+    // returns the command and its whole payload once the last chunk has arrived
+    pub fn receive(&mut self, command: Command, payload: &str) -> Option<(Command, String)> {
+        let more = command.more;
+
+        let (first, mut data) = match self.pending.take() {
+            Some(pending) => pending,
+            None => (command, String::new()),
+        };
+
+        if data.len() + payload.len() > MAX_DATA {
+            return None;
+        }
+        data.push_str(payload);
+
+        if more {
+            self.pending = Some((first, data));
+            None
+        } else {
+            Some((first, data))
+        }
+    }
+}
+
+// the reply when a file can't be read, which doesn't say why so programs can't use it to find
+// out what's on the system
+const UNREADABLE: &str = "EBADF:unable to read file";
+
+// like kitty, files under these are never read, they can hang or expose the system
+const FORBIDDEN_DIRS: [&str; 3] = ["/proc", "/sys", "/dev"];
+
+// the canonical path of a file a program asked for, if it may be read
+fn allowed_path(name: &str) -> Result<PathBuf, String> {
+    let path = std::fs::canonicalize(name).map_err(|_| UNREADABLE.to_string())?;
+
+    if FORBIDDEN_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return Err(UNREADABLE.to_string());
+    }
+
+    Ok(path)
+}
+
+// reads a regular file, opened without blocking so a FIFO or a device can't hang the window
+fn read_file(path: &Path, command: &Command) -> Result<Vec<u8>, String> {
+    let read = || -> std::io::Result<Vec<u8>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .custom_flags(nix::libc::O_NONBLOCK)
+            .open(path)?;
+        if !file.metadata()?.is_file() {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        }
+        file.seek(SeekFrom::Start(command.offset as u64))?;
+
+        let limit = if command.size > 0 {
+            command.size
+        } else {
+            MAX_DATA
+        };
+
+        let mut data = vec![];
+        file.take(limit as u64).read_to_end(&mut data)?;
+        Ok(data)
+    };
+
+    read().map_err(|_| UNREADABLE.to_string())
+}
+
+// whether `path` is a file a program wrote to a temporary directory for the terminal to read and
+// delete
+fn is_temporary_file(path: &Path) -> bool {
+    let named = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains("tty-graphics-protocol"));
+
+    let temp_dirs = [std::env::temp_dir(), PathBuf::from("/tmp")];
+    let in_temp_dir = temp_dirs
+        .iter()
+        .filter_map(|dir| std::fs::canonicalize(dir).ok())
+        .any(|dir| path.parent() == Some(dir.as_path()));
+
+    named && in_temp_dir
+}
+
+// the raw image data of a transmission, read from wherever the medium says it is
+fn read_data(command: &Command, payload: &str) -> Result<Vec<u8>, String> {
+    let payload = STANDARD
+        .decode(payload)
+        .map_err(|_| "EINVAL:bad base64 data".to_string())?;
+
+    if command.medium == 'd' {
+        return Ok(payload);
+    }
+
+    let name = String::from_utf8(payload).map_err(|_| "EINVAL:bad file name".to_string())?;
+
+    match command.medium {
+        'f' => read_file(&allowed_path(&name)?, command),
+        't' => {
+            let path = allowed_path(&name)?;
+            if !is_temporary_file(&path) {
+                return Err("EPERM:not a temporary file".to_string());
+            }
+
+            // reading it confirmed it's a regular file
+            let data = read_file(&path, command)?;
+            let _ = std::fs::remove_file(&path);
+            Ok(data)
+        }
+        's' => {
+            let shm_name = name.trim_start_matches('/');
+            if shm_name.is_empty() || shm_name.contains('/') || shm_name == "." || shm_name == ".."
+            {
+                return Err("EINVAL:bad shared memory name".to_string());
+            }
+
+            let data = read_file(&Path::new("/dev/shm").join(shm_name), command);
+            let _ = nix::sys::mman::shm_unlink(name.as_str());
+            data
+        }
+        _ => Err("EINVAL:unknown transmission medium".to_string()),
+    }
+}
+
+// reads and decodes the image a transmission describes
+pub fn load(command: &Command, payload: &str) -> Result<Image, String> {
+    let mut data = read_data(command, payload)?;
+
+    if command.compressed {
+        let mut decompressed = vec![];
+        flate2::read::ZlibDecoder::new(&data[..])
+            .take(MAX_DATA as u64)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| format!("EINVAL:{}", e))?;
+        data = decompressed;
+    }
+
+    match command.format {
+        100 => {
+            let image = image::load_from_memory_with_format(&data, image::ImageFormat::Png)
+                .map_err(|e| format!("EBADPNG:{}", e))?
+                .to_rgba8();
+            Ok(Image::new(image.width(), image.height(), image.into_raw()))
+        }
+        24 | 32 => {
+            let (width, height) = (command.width as usize, command.height as usize);
+            let channels = command.format as usize / 8;
+
+            let bytes = width
+                .checked_mul(height)
+                .and_then(|pixels| pixels.checked_mul(channels))
+                .ok_or_else(|| "EINVAL:image too large".to_string())?;
+            if width == 0 || height == 0 || data.len() < bytes {
+                return Err("ENODATA:insufficient image data".to_string());
+            }
+
+            let pixels = if channels == 4 {
+                data.truncate(bytes);
+                data
+            } else {
+                data.chunks(3)
+                    .take(width * height)
+                    .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect()
+            };
+
+            Ok(Image::new(width as u32, height as u32, pixels))
+        }
+        _ => Err("EINVAL:unknown format".to_string()),
+    }
+}
+
+// the reply to a command, kitty only replies when an id or number was given
+pub fn reply(command: &Command, result: &Result<(), String>) -> Option<String> {
+    if command.id == 0 && command.number == 0 {
+        return None;
+    }
+
+    let message = match result {
+        Ok(()) if command.quiet == 0 => "OK".to_string(),
+        Err(e) if command.quiet < 2 => e.clone(),
+        _ => return None,
+    };
+
+    let mut keys = vec![];
+    if command.id != 0 {
+        keys.push(format!("i={}", command.id));
+    }
+    if command.number != 0 {
+        keys.push(format!("I={}", command.number));
+    }
+    if command.placement_id != 0 {
+        keys.push(format!("p={}", command.placement_id));
+    }
+
+    Some(format!("\x1b_G{};{}\x1b\\", keys.join(","), message))
+}
+
+// removes the placements a delete command selects, uppercase variants also free the images
+// that are no longer placed
+//
+// `cursor` is the cursor as (column, line), `top_line` the line at the top of the screen and
+// `cell_size` is used to find which cells placements cover
+pub fn delete(
+    command: &Command,
+    placements: &mut Vec<Placement>,
+    store: &mut ImageStore,
+    cursor: (u32, usize),
+    top_line: usize,
+    cell_size: (f32, f32),
+) {
+    let covers = |p: &Placement, col: Option<u32>, line: Option<usize>| {
+        let cols = (p.width / cell_size.0).ceil().max(1.0) as u32;
+        let lines = (p.height / cell_size.1).ceil().max(1.0) as usize;
+
+        col.is_none_or(|col| col >= p.col && col < p.col + cols)
+            && line.is_none_or(|line| line >= p.line && line < p.line + lines)
+    };
+
+    // x and y are 1 based cell coordinates on the screen
+    let x = command.source[0].saturating_sub(1);
+    let y = top_line + command.source[1].saturating_sub(1) as usize;
+
+    let selects = |p: &Placement| match command.delete.to_ascii_lowercase() {
+        'a' => true,
+        'i' => {
+            p.image_id == command.id
+                && (command.placement_id == 0 || p.placement_id == command.placement_id)
+        }
+        'c' => covers(p, Some(cursor.0), Some(cursor.1)),
+        'p' => covers(p, Some(x), Some(y)),
+        'x' => covers(p, Some(x), None),
+        'y' => covers(p, None, Some(y)),
+        'z' => p.z_index == command.z_index,
+        _ => false,
+    };
+
+    let (removed, kept): (Vec<Placement>, Vec<Placement>) =
+        placements.drain(..).partition(|p| selects(p));
+    *placements = kept;
+
+    if command.delete.is_ascii_uppercase() {
+        let mut ids = removed.iter().map(|p| p.image_id).collect::<Vec<u32>>();
+        if command.delete == 'I' {
+            ids.push(command.id);
+        }
+
+        for id in ids {
+            if !placements.iter().any(|p| p.image_id == id) {
+                store.remove(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let command = Command::parse("a=p,i=3,p=7,c=4,z=-2,C=1");
+
+        assert_eq!(command.action, 'p');
+        assert_eq!((command.id, command.placement_id), (3, 7));
+        assert_eq!(command.cols, 4);
+        assert_eq!(command.z_index, -2);
+        assert!(command.no_cursor_move);
+    }
+
+    #[test]
+    fn test_reply() {
+        let command = Command::parse("i=3,I=4");
+
+        assert_eq!(
+            reply(&command, &Ok(())),
+            Some("\x1b_Gi=3,I=4;OK\x1b\\".to_string())
+        );
+        assert_eq!(reply(&Command::parse("q=1,i=3"), &Ok(())), None);
+        assert_eq!(
+            reply(&Command::default(), &Err("ENOENT:x".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let file = |name: &str| STANDARD.encode(name);
+        let dir = std::env::temp_dir().join(format!("nyat-kitty-{}", std::process::id()));
+        let marked = dir.join("tty-graphics-protocol");
+        std::fs::create_dir_all(&marked).unwrap();
+
+        // the name and directory are checked after `..` is resolved
+        let secret = dir.join("secret");
+        std::fs::write(&secret, [1, 2, 3, 4]).unwrap();
+        let escape = format!("{}/../secret", marked.display());
+        assert_eq!(
+            read_data(&Command::parse("t=t"), &file(&escape)),
+            Err("EPERM:not a temporary file".to_string())
+        );
+        assert!(secret.exists());
+
+        let temp =
+            std::env::temp_dir().join(format!("tty-graphics-protocol-{}", std::process::id()));
+        std::fs::write(&temp, [1, 2, 3, 4]).unwrap();
+        assert_eq!(
+            read_data(&Command::parse("t=t"), &file(&temp.to_string_lossy())),
+            Ok(vec![1, 2, 3, 4])
+        );
+        assert!(!temp.exists());
+
+        // devices, FIFOs and the like fail without saying why, and without blocking
+        let fifo = dir.join("fifo");
+        nix::unistd::mkfifo(&fifo, nix::sys::stat::Mode::S_IRWXU).unwrap();
+        for name in [
+            fifo.to_str().unwrap(),
+            "/dev/zero",
+            "/proc/self/environ",
+            "/missing",
+        ] {
+            assert_eq!(
+                read_data(&Command::parse("t=f"), &file(name)),
+                Err(UNREADABLE.to_string())
+            );
+        }
+        assert_eq!(
+            read_data(&Command::parse("t=s"), &file("../../etc/passwd")),
+            Err("EINVAL:bad shared memory name".to_string())
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_size_overflow() {
+        let command = Command::parse(&format!("f=32,s={},v={}", u32::MAX, u32::MAX));
+        assert_eq!(
+            load(&command, &STANDARD.encode([0; 16])),
+            Err("EINVAL:image too large".to_string())
+        );
+    }
+
+    #[test]
+    fn test_retransmit() {
+        // a new image under an id the renderer already uploaded has to be uploaded again
+        let mut store = ImageStore::default();
+        store.insert(7, Image::new(1, 1, vec![0; 4]));
+        let first = store.generation(7);
+        store.insert(7, Image::new(1, 1, vec![255; 4]));
+
+        assert_ne!(store.generation(7), first);
+        assert_eq!(store.get(7).unwrap().pixels, vec![255; 4]);
+    }
+
+    #[test]
+    fn test_delete_at_cursor() {
+        let mut store = ImageStore::default();
+        let id = store.add_kept(Image::new(1, 1, vec![0; 4]));
+        let mut placements = vec![
+            Placement::new(id, 10, 2, 20.0, 20.0),
+            Placement::new(id, 14, 0, 10.0, 10.0),
+        ];
+
+        kitty_delete(&mut placements, &mut store, "d=C");
+
+        assert_eq!(placements.len(), 1);
+        assert_eq!(placements[0].line, 14);
+        assert!(store.get(id).is_some());
+    }
+
+    fn kitty_delete(placements: &mut Vec<Placement>, store: &mut ImageStore, control: &str) {
+        delete(
+            &Command::parse(control),
+            placements,
+            store,
+            (3, 11),
+            10,
+            (10.0, 10.0),
+        );
+    }
+}
@@ -0,0 +1,142 @@
+// images shown inline in the terminal, decoded into RGBA pixel buffers that the renderer uploads
+use std::collections::{HashMap, HashSet};
+
+pub mod iterm;
+pub mod kitty;
+pub mod sixel;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    // RGBA, row by row
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+// an image shown on the grid with its top left corner in a cell, `line` is a grid line number
+// so the image scrolls along with the text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub image_id: u32,
+    // set by kitty graphics clients to refer to this placement later, 0 when unset
+    pub placement_id: u32,
+    pub line: usize,
+    pub col: u32,
+    // pixel offset from the top left of the cell
+    pub offset: (f32, f32),
+    // size in pixels the image is drawn at
+    pub width: f32,
+    pub height: f32,
+    // the x, y, width and height in pixels of the part of the image shown, all of it if unset
+    pub source: Option<[u32; 4]>,
+    // negative values are drawn beneath the text
+    pub z_index: i32,
+}
+
+impl Placement {
+    pub fn new(image_id: u32, line: usize, col: u32, width: f32, height: f32) -> Self {
+        Self {
+            image_id,
+            placement_id: 0,
+            line,
+            col,
+            offset: (0.0, 0.0),
+            width,
+            height,
+            source: None,
+            z_index: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImageStore {
+    // each image with the generation it was stored in, which changes when its id is reused
+    images: HashMap<u32, (u64, Image)>,
+    generation: u64,
+    // images that are dropped as soon as nothing places them
+    anonymous: HashSet<u32>,
+    // ids are handed out counting down so they don't collide with the small ids kitty
+    // graphics clients pick
+    used_ids: u32,
+}
+
+impl ImageStore {
+    fn store(&mut self, id: u32, image: Image) {
+        self.generation += 1;
+        self.images.insert(id, (self.generation, image));
+    }
+
+    fn next_id(&mut self) -> u32 {
+        loop {
+            self.used_ids = self.used_ids.wrapping_add(1);
+            let id = u32::MAX - self.used_ids;
+            if id != 0 && !self.images.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    // stores an image under a fresh id, to be dropped once it is no longer placed
+    pub fn add(&mut self, image: Image) -> u32 {
+        let id = self.next_id();
+        self.store(id, image);
+        self.anonymous.insert(id);
+        id
+    }
+
+    // stores an image under a fresh id until it is removed
+    pub fn add_kept(&mut self, image: Image) -> u32 {
+        let id = self.next_id();
+        self.store(id, image);
+        id
+    }
+
+    // stores an image under the given id until it is removed, replacing any image it had
+    pub fn insert(&mut self, id: u32, image: Image) {
+        self.anonymous.remove(&id);
+        self.store(id, image);
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        self.anonymous.remove(&id);
+        self.images.remove(&id);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Image> {
+        self.images.get(&id).map(|(_, image)| image)
+    }
+
+    // changes whenever a new image is stored under `id`
+    pub fn generation(&self, id: u32) -> Option<u64> {
+        self.images.get(&id).map(|(generation, _)| *generation)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &u32> {
+        self.images.keys()
+    }
+
+    // drops anonymous images that aren't in `placed` anymore
+    pub fn prune(&mut self, placed: &HashSet<u32>) {
+        let unused = self
+            .anonymous
+            .iter()
+            .filter(|id| !placed.contains(id))
+            .copied()
+            .collect::<Vec<u32>>();
+
+        for id in unused {
+            self.remove(id);
+        }
+    }
+}
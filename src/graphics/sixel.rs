@@ -0,0 +1,294 @@
+// decodes the data of a sixel `DCS P1;P2;P3 q data ST` sequence into an image
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::Image;
+
+// images larger than this in either direction are cut off
+const MAX_SIZE: usize = 4096;
+
+// the most memory the pixels of one image may take
+const MAX_BYTES: usize = MAX_SIZE * MAX_SIZE * 4;
+
+// the VT340 default color registers, in percent
+const DEFAULT_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+struct Canvas {
+    rows: Vec<Vec<[u8; 4]>>,
+    background: [u8; 4],
+}
+
+impl Canvas {
+    fn set(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        if x >= MAX_SIZE || y >= MAX_SIZE {
+            return;
+        }
+
+        if self.rows.len() <= y {
+            self.rows.resize(y + 1, vec![]);
+        }
+
+        let row = &mut self.rows[y];
+        if row.len() <= x {
+            row.resize(x + 1, self.background);
+        }
+        row[x] = color;
+    }
+}
+
+fn percent_to_byte(percent: u16) -> u8 {
+    (percent.min(100) as u32 * 255 / 100) as u8
+}
+
+// sixel HLS puts blue at 0 degrees, red at 120 and green at 240
+fn hls_to_rgb(hue: u16, lightness: u16, saturation: u16) -> [u8; 3] {
+    let h = ((hue as f32 + 240.0) % 360.0) / 360.0;
+    let l = lightness.min(100) as f32 / 100.0;
+    let s = saturation.min(100) as f32 / 100.0;
+
+    if s == 0.0 {
+        let v = (l * 255.0).round() as u8;
+        return [v, v, v];
+    }
+
+    let q = if l < 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+
+    let channel = |t: f32| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+
+    [channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0)]
+}
+
+fn read_params(chars: &mut Peekable<Chars>) -> Vec<u16> {
+    let mut params = vec![];
+    let mut current: Option<u16> = None;
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '0'..='9' => {
+                let digit = c as u16 - '0' as u16;
+                current = Some(
+                    current
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+            }
+            ';' => params.push(current.take().unwrap_or(0)),
+            _ => break,
+        }
+        chars.next();
+    }
+
+    if let Some(n) = current {
+        params.push(n);
+    }
+
+    params
+}
+
+// `params` are the DCS parameters, of which P2 = 1 leaves unset pixels transparent
+pub fn decode(params: &[u16], data: &str) -> Image {
+    let background = if params.get(1) == Some(&1) {
+        [0, 0, 0, 0]
+    } else {
+        [0, 0, 0, 255]
+    };
+
+    let mut palette = [[0u8, 0, 0, 255]; 256];
+    for (i, [r, g, b]) in DEFAULT_PALETTE.iter().enumerate() {
+        palette[i] = [
+            percent_to_byte(*r as u16),
+            percent_to_byte(*g as u16),
+            percent_to_byte(*b as u16),
+            255,
+        ];
+    }
+
+    let mut canvas = Canvas {
+        rows: vec![],
+        background,
+    };
+    let mut raster = (0, 0);
+    let mut color = 0;
+    let (mut x, mut y) = (0, 0);
+
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        let (sixel, count) = match c {
+            // raster attributes, `"Pan;Pad;Ph;Pv`
+            '"' => {
+                let p = read_params(&mut chars);
+                if p.len() >= 4 {
+                    raster = (p[2] as usize, p[3] as usize);
+                }
+                continue;
+            }
+            // `#Pc` selects a color register, `#Pc;Pu;Px;Py;Pz` also defines it
+            '#' => {
+                let p = read_params(&mut chars);
+                if let Some(&register) = p.first() {
+                    color = register as usize % palette.len();
+                }
+                if p.len() == 5 {
+                    let [r, g, b] = match p[1] {
+                        1 => hls_to_rgb(p[2], p[3], p[4]),
+                        _ => [
+                            percent_to_byte(p[2]),
+                            percent_to_byte(p[3]),
+                            percent_to_byte(p[4]),
+                        ],
+                    };
+                    palette[color] = [r, g, b, 255];
+                }
+                continue;
+            }
+            '!' => {
+                let count = read_params(&mut chars).first().copied().unwrap_or(1);
+                match chars.next() {
+                    Some(c @ '?'..='~') => (c, count.max(1) as usize),
+                    _ => continue,
+                }
+            }
+            '$' => {
+                x = 0;
+                continue;
+            }
+            '-' => {
+                x = 0;
+                y += 6;
+                continue;
+            }
+            '?'..='~' => (c, 1),
+            _ => continue,
+        };
+
+        let bits = sixel as u8 - b'?';
+        for _ in 0..count.min(MAX_SIZE) {
+            for bit in 0..6 {
+                if bits & (1 << bit) != 0 {
+                    canvas.set(x, y + bit, palette[color]);
+                }
+            }
+            x += 1;
+        }
+    }
+
+    // the raster attributes can only crop what was drawn, so a large size with no data behind it
+    // doesn't allocate anything
+    let drawn_width = canvas.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let width = if raster.0 > 0 {
+        raster.0.min(drawn_width)
+    } else {
+        drawn_width
+    };
+    let height = if raster.1 > 0 {
+        raster.1.min(canvas.rows.len())
+    } else {
+        canvas.rows.len()
+    };
+
+    let bytes = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .filter(|&bytes| bytes <= MAX_BYTES);
+    let Some(bytes) = bytes else {
+        return Image::new(0, 0, vec![]);
+    };
+
+    let mut pixels = Vec::with_capacity(bytes);
+    for row in 0..height {
+        for col in 0..width {
+            let pixel = canvas
+                .rows
+                .get(row)
+                .and_then(|r| r.get(col))
+                .unwrap_or(&background);
+            pixels.extend_from_slice(pixel);
+        }
+    }
+
+    Image::new(width as u32, height as u32, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // a red 2x6 block, then a green pixel below it
+        let image = decode(&[0, 1], "#1;2;100;0;0#1~~-#2;2;0;100;0@");
+
+        assert_eq!((image.width, image.height), (2, 7));
+
+        let pixel = |x: usize, y: usize| {
+            let i = (y * image.width as usize + x) * 4;
+            &image.pixels[i..i + 4]
+        };
+        assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(1, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(0, 6), [0, 255, 0, 255]);
+        assert_eq!(pixel(1, 6), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decode_repeat_and_raster() {
+        let image = decode(&[], "\"1;1;4;2#0;2;0;0;100!4~");
+
+        assert_eq!((image.width, image.height), (4, 2));
+        assert!(image
+            .pixels
+            .chunks(4)
+            .all(|pixel| pixel == [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_raster_without_data() {
+        let image = decode(&[], "\"1;1;10000;10000");
+        assert_eq!((image.width, image.height), (0, 0));
+        assert!(image.pixels.is_empty());
+
+        let image = decode(&[], "\"1;1;8;8#0;2;0;0;100~");
+        assert_eq!((image.width, image.height), (1, 6));
+    }
+
+    #[test]
+    fn test_hls() {
+        assert_eq!(hls_to_rgb(120, 50, 100), [255, 0, 0]);
+        assert_eq!(hls_to_rgb(0, 50, 100), [0, 0, 255]);
+    }
+}
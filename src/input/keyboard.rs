@@ -0,0 +1,722 @@
+// encodes key presses the way xterm does
+//
+// keys that produce text arrive as characters through `WindowEvent::ReceivedCharacter`, so the
+// keyboard layout is respected. everything else, including control characters, is encoded here
+// from the key itself.
+//
+// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-PC-Style-Function-Keys
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::terminal::Modes;
+
+// the xterm modifier parameter, 1 when no modifiers are held
+fn modifier_param(modifiers: ModifiersState) -> u8 {
+    1 + u8::from(modifiers.shift())
+        + 2 * u8::from(modifiers.alt())
+        + 4 * u8::from(modifiers.ctrl())
+        + 8 * u8::from(modifiers.logo())
+}
+
+// `CSI final`, or `CSI 1 ; m final` with modifiers. `ss3` keys send `SS3 final` without them
+fn letter_key(last: char, modifiers: ModifiersState, ss3: bool) -> String {
+    match modifier_param(modifiers) {
+        1 if ss3 => format!("\x1bO{}", last),
+        1 => format!("\x1b[{}", last),
+        m => format!("\x1b[1;{}{}", m, last),
+    }
+}
+
+// `CSI n ~`, or `CSI n ; m ~` with modifiers
+fn tilde_key(n: u8, modifiers: ModifiersState) -> String {
+    match modifier_param(modifiers) {
+        1 => format!("\x1b[{}~", n),
+        m => format!("\x1b[{};{}~", n, m),
+    }
+}
+
+// the C0 control character Ctrl turns a key into
+fn control_character(key: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    if let Some(i) = letters.iter().position(|&letter| letter == key) {
+        return char::from_u32(i as u32 + 1);
+    }
+
+    match key {
+        Space | Key2 | At => Some('\x00'),
+        Key3 | LBracket => Some('\x1b'),
+        Key4 | Backslash => Some('\x1c'),
+        Key5 | RBracket => Some('\x1d'),
+        Key6 | Caret => Some('\x1e'),
+        Key7 | Slash | Minus => Some('\x1f'),
+        Key8 => Some('\x7f'),
+        _ => None,
+    }
+}
+
+// the final character the keypad sends after `SS3` in application keypad mode
+fn keypad_character(key: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let digits = [
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    ];
+    if let Some(i) = digits.iter().position(|&digit| digit == key) {
+        return char::from_u32('p' as u32 + i as u32);
+    }
+
+    match key {
+        NumpadEnter => Some('M'),
+        NumpadEquals => Some('X'),
+        NumpadMultiply => Some('j'),
+        NumpadAdd => Some('k'),
+        NumpadComma => Some('l'),
+        NumpadSubtract => Some('m'),
+        NumpadDecimal => Some('n'),
+        NumpadDivide => Some('o'),
+        _ => None,
+    }
+}
+
+// xterm's modifyOtherKeys, `CSI 27 ; mod ; code ~` for modified keys the usual encoding loses.
+// level 1 leaves keys that already send something distinct alone, level 2 only leaves keys
+// that type text with Shift. the code is the character the layout produced, `text`, and only
+// when Ctrl turned that into a control character is it made up from the key
+fn modify_other_keys(
+    key: VirtualKeyCode,
+    modifiers: ModifiersState,
+    text: Option<char>,
+    level: u8,
+) -> Option<String> {
+    use VirtualKeyCode::*;
+
+    let m = modifier_param(modifiers);
+    let special = matches!(key, Return | NumpadEnter | Tab | Back | Escape);
+    let code = match key {
+        Return | NumpadEnter => 13,
+        Tab => 9,
+        Back => 127,
+        Escape => 27,
+        _ => match text.filter(|c| !c.is_control()) {
+            Some(c) => c as u32,
+            None => {
+                let c = key_character(key)?;
+                if modifiers.shift() {
+                    c.to_ascii_uppercase() as u32
+                } else {
+                    c as u32
+                }
+            }
+        },
+    };
+
+    let modified = match level {
+        _ if m == 1 => false,
+        _ if key == Tab && m == 2 => false,
+        1 if special => modifiers.ctrl() || modifiers.shift(),
+        1 => modifiers.ctrl() && (modifiers.shift() || control_character(key).is_none()),
+        _ => special || m != 2,
+    };
+
+    modified.then(|| format!("\x1b[27;{};{}~", m, code))
+}
+
+// what pressing `key` sends, None for keys left to the text they produce
+pub fn encode(key: VirtualKeyCode, modifiers: ModifiersState, modes: &Modes) -> Option<String> {
+    encode_key(key, modifiers, None, modes)
+}
+
+// what a key that produces text sends once its character is known, which is the text itself
+// unless the key is encoded
+pub fn encode_typed(event: &KeyEvent, modes: &Modes) -> Option<String> {
+    event
+        .key
+        .and_then(|key| encode_key(key, event.modifiers, event.text, modes))
+        .or_else(|| encode_char(event.text?, event.modifiers))
+}
+
+fn encode_key(
+    key: VirtualKeyCode,
+    modifiers: ModifiersState,
+    text: Option<char>,
+    modes: &Modes,
+) -> Option<String> {
+    use VirtualKeyCode::*;
+
+    if modes.application_keypad && modifier_param(modifiers) == 1 {
+        if let Some(c) = keypad_character(key) {
+            return Some(format!("\x1bO{}", c));
+        }
+    }
+
+    if modes.modify_other_keys > 0 {
+        if let Some(sequence) = modify_other_keys(key, modifiers, text, modes.modify_other_keys) {
+            return Some(sequence);
+        }
+    }
+
+    let cursor = modes.application_cursor;
+
+    // Alt sends ESC before keys that aren't encoded with a modifier parameter
+    let alt = |s: &str| {
+        if modifiers.alt() {
+            format!("\x1b{}", s)
+        } else {
+            s.to_string()
+        }
+    };
+
+    let sequence = match key {
+        Up => letter_key('A', modifiers, cursor),
+        Down => letter_key('B', modifiers, cursor),
+        Right => letter_key('C', modifiers, cursor),
+        Left => letter_key('D', modifiers, cursor),
+        Home => letter_key('H', modifiers, cursor),
+        End => letter_key('F', modifiers, cursor),
+        Insert => tilde_key(2, modifiers),
+        Delete => tilde_key(3, modifiers),
+        PageUp => tilde_key(5, modifiers),
+        PageDown => tilde_key(6, modifiers),
+        F1 => letter_key('P', modifiers, true),
+        F2 => letter_key('Q', modifiers, true),
+        F3 => letter_key('R', modifiers, true),
+        F4 => letter_key('S', modifiers, true),
+        F5 => tilde_key(15, modifiers),
+        F6 => tilde_key(17, modifiers),
+        F7 => tilde_key(18, modifiers),
+        F8 => tilde_key(19, modifiers),
+        F9 => tilde_key(20, modifiers),
+        F10 => tilde_key(21, modifiers),
+        F11 => tilde_key(23, modifiers),
+        F12 => tilde_key(24, modifiers),
+        F13 => tilde_key(25, modifiers),
+        F14 => tilde_key(26, modifiers),
+        F15 => tilde_key(28, modifiers),
+        F16 => tilde_key(29, modifiers),
+        F17 => tilde_key(31, modifiers),
+        F18 => tilde_key(32, modifiers),
+        F19 => tilde_key(33, modifiers),
+        F20 => tilde_key(34, modifiers),
+        Tab if modifiers.shift() => "\x1b[Z".to_string(),
+        Tab => alt("\t"),
+        Return | NumpadEnter => alt("\r"),
+        Back if modifiers.ctrl() => alt("\x08"),
+        Back => alt("\x7f"),
+        Escape => alt("\x1b"),
+        _ if modifiers.ctrl() => alt(&control_character(key)?.to_string()),
+        _ => return None,
+    };
+
+    Some(sequence)
+}
+
+// what typing `c` sends, control characters were already sent by `encode`
+pub fn encode_char(c: char, modifiers: ModifiersState) -> Option<String> {
+    if c.is_control() {
+        return None;
+    }
+
+    if modifiers.alt() {
+        Some(format!("\x1b{}", c))
+    } else {
+        Some(c.to_string())
+    }
+}
+
+// the progressive enhancement flags of the kitty keyboard protocol
+//
+// https://sw.kovidgoyal.net/kitty/keyboard-protocol/
+pub const DISAMBIGUATE: u32 = 1;
+pub const REPORT_EVENT_TYPES: u32 = 2;
+pub const REPORT_ALTERNATE_KEYS: u32 = 4;
+pub const REPORT_ALL_KEYS: u32 = 8;
+pub const REPORT_TEXT: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyEventKind {
+    Press,
+    Repeat,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEvent {
+    pub key: Option<VirtualKeyCode>,
+    pub modifiers: ModifiersState,
+    pub kind: KeyEventKind,
+    // the text the key produced, known once the character for it arrives
+    pub text: Option<char>,
+}
+
+// the character a key produces without modifiers on a US layout
+fn key_character(key: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    if let Some(i) = letters.iter().position(|&letter| letter == key) {
+        return char::from_u32('a' as u32 + i as u32);
+    }
+
+    let digits = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    if let Some(i) = digits.iter().position(|&digit| digit == key) {
+        return char::from_u32('0' as u32 + i as u32);
+    }
+
+    match key {
+        Space => Some(' '),
+        Minus => Some('-'),
+        Equals => Some('='),
+        LBracket => Some('['),
+        RBracket => Some(']'),
+        Backslash => Some('\\'),
+        Semicolon => Some(';'),
+        Apostrophe => Some('\''),
+        Grave => Some('`'),
+        Comma => Some(','),
+        Period => Some('.'),
+        Slash => Some('/'),
+        _ => None,
+    }
+}
+
+// the number and final character of keys that don't produce text
+fn functional_key(key: VirtualKeyCode) -> Option<(u32, char)> {
+    use VirtualKeyCode::*;
+
+    let f13 = [F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24];
+    if let Some(i) = f13.iter().position(|&f| f == key) {
+        return Some((57376 + i as u32, 'u'));
+    }
+
+    let keypad = [
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadDecimal,
+        NumpadDivide,
+        NumpadMultiply,
+        NumpadSubtract,
+        NumpadAdd,
+        NumpadEnter,
+        NumpadEquals,
+        NumpadComma,
+    ];
+    if let Some(i) = keypad.iter().position(|&k| k == key) {
+        return Some((57399 + i as u32, 'u'));
+    }
+
+    let key = match key {
+        Escape => (27, 'u'),
+        Return => (13, 'u'),
+        Tab => (9, 'u'),
+        Back => (127, 'u'),
+        Insert => (2, '~'),
+        Delete => (3, '~'),
+        Left => (1, 'D'),
+        Right => (1, 'C'),
+        Up => (1, 'A'),
+        Down => (1, 'B'),
+        PageUp => (5, '~'),
+        PageDown => (6, '~'),
+        Home => (1, 'H'),
+        End => (1, 'F'),
+        Capital => (57358, 'u'),
+        Scroll => (57359, 'u'),
+        Numlock => (57360, 'u'),
+        Snapshot => (57361, 'u'),
+        Pause => (57362, 'u'),
+        Apps => (57363, 'u'),
+        F1 => (1, 'P'),
+        F2 => (1, 'Q'),
+        F3 => (13, '~'),
+        F4 => (1, 'S'),
+        F5 => (15, '~'),
+        F6 => (17, '~'),
+        F7 => (18, '~'),
+        F8 => (19, '~'),
+        F9 => (20, '~'),
+        F10 => (21, '~'),
+        F11 => (23, '~'),
+        F12 => (24, '~'),
+        PlayPause => (57430, 'u'),
+        MediaStop => (57432, 'u'),
+        NextTrack => (57435, 'u'),
+        PrevTrack => (57436, 'u'),
+        VolumeDown => (57438, 'u'),
+        VolumeUp => (57439, 'u'),
+        Mute => (57440, 'u'),
+        LShift => (57441, 'u'),
+        LControl => (57442, 'u'),
+        LAlt => (57443, 'u'),
+        LWin => (57444, 'u'),
+        RShift => (57447, 'u'),
+        RControl => (57448, 'u'),
+        RAlt => (57449, 'u'),
+        RWin => (57450, 'u'),
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+fn is_modifier(key: VirtualKeyCode) -> bool {
+    use VirtualKeyCode::*;
+
+    matches!(
+        key,
+        LShift | LControl | LAlt | LWin | RShift | RControl | RAlt | RWin
+    )
+}
+
+// whether a key produces text, so it has to wait for its character before being encoded
+pub fn produces_text(key: Option<VirtualKeyCode>) -> bool {
+    key.is_none_or(|key| functional_key(key).is_none())
+}
+
+// what a key event sends with the kitty keyboard protocol `flags` in effect, keys that aren't
+// escaped are sent the legacy way with the terminal's `modes`
+pub fn encode_kitty(event: &KeyEvent, flags: u32, modes: &Modes) -> Option<String> {
+    use VirtualKeyCode::*;
+
+    let all_keys = flags & REPORT_ALL_KEYS != 0;
+    let modifiers = modifier_param(event.modifiers);
+    let functional = event.key.and_then(functional_key);
+
+    let (number, last) = match functional {
+        Some(key) => key,
+        None => {
+            let c = event
+                .key
+                .and_then(key_character)
+                .or_else(|| event.text.and_then(|c| c.to_lowercase().next()))?;
+            (c as u32, 'u')
+        }
+    };
+
+    // Enter, Tab and Backspace stay as they were so a shell still works after a program that
+    // turned the protocol on crashed
+    let escaped = all_keys
+        || (flags & DISAMBIGUATE != 0
+            && match event.key {
+                Some(Return | Tab | Back) => modifiers != 1,
+                Some(key) if is_modifier(key) => false,
+                _ if functional.is_some() => true,
+                _ => event.modifiers.ctrl() || event.modifiers.alt() || event.modifiers.logo(),
+            });
+
+    if !escaped {
+        if event.kind == KeyEventKind::Release {
+            return None;
+        }
+        return encode_typed(event, modes);
+    }
+
+    let event_type = match event.kind {
+        _ if flags & REPORT_EVENT_TYPES == 0 => 1,
+        KeyEventKind::Press => 1,
+        KeyEventKind::Repeat => 2,
+        KeyEventKind::Release => 3,
+    };
+    if event.kind == KeyEventKind::Release
+        && (event_type == 1 || (!all_keys && matches!(event.key, Some(Return | Tab | Back))))
+    {
+        return None;
+    }
+
+    let mut sequence = number.to_string();
+
+    if flags & REPORT_ALTERNATE_KEYS != 0 && last == 'u' {
+        if let Some(shifted) = event
+            .text
+            .filter(|&c| event.modifiers.shift() && c as u32 != number)
+        {
+            sequence += &format!(":{}", shifted as u32);
+        }
+    }
+
+    let modifier_field = match event_type {
+        1 if modifiers == 1 => String::new(),
+        1 => modifiers.to_string(),
+        event_type => format!("{}:{}", modifiers, event_type),
+    };
+    let text = event
+        .text
+        .filter(|c| all_keys && flags & REPORT_TEXT != 0 && !c.is_control())
+        .filter(|_| event.kind != KeyEventKind::Release);
+
+    if !modifier_field.is_empty() || text.is_some() {
+        sequence += &format!(";{}", modifier_field);
+    }
+    if let Some(text) = text {
+        sequence += &format!(";{}", text as u32);
+    }
+
+    // the keys with legacy letter sequences leave out a lone 1
+    if last != 'u' && last != '~' && sequence == "1" {
+        sequence.clear();
+    }
+
+    Some(format!("\x1b[{}{}", sequence, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(key: VirtualKeyCode, modifiers: ModifiersState) -> Option<String> {
+        super::encode(key, modifiers, &Modes::default())
+    }
+
+    #[test]
+    fn test_encode() {
+        let none = ModifiersState::empty();
+
+        assert_eq!(encode(VirtualKeyCode::Up, none).as_deref(), Some("\x1b[A"));
+        assert_eq!(
+            encode(VirtualKeyCode::Up, ModifiersState::CTRL).as_deref(),
+            Some("\x1b[1;5A")
+        );
+        assert_eq!(encode(VirtualKeyCode::F1, none).as_deref(), Some("\x1bOP"));
+        assert_eq!(
+            encode(VirtualKeyCode::F5, ModifiersState::SHIFT).as_deref(),
+            Some("\x1b[15;2~")
+        );
+        assert_eq!(encode(VirtualKeyCode::Return, none).as_deref(), Some("\r"));
+        assert_eq!(encode(VirtualKeyCode::Back, none).as_deref(), Some("\x7f"));
+        assert_eq!(
+            encode(VirtualKeyCode::Tab, ModifiersState::SHIFT).as_deref(),
+            Some("\x1b[Z")
+        );
+        assert_eq!(encode(VirtualKeyCode::A, none), None);
+    }
+
+    #[test]
+    fn test_encode_control() {
+        assert_eq!(
+            encode(VirtualKeyCode::C, ModifiersState::CTRL).as_deref(),
+            Some("\x03")
+        );
+        assert_eq!(
+            encode(VirtualKeyCode::LBracket, ModifiersState::CTRL).as_deref(),
+            Some("\x1b")
+        );
+        assert_eq!(
+            encode(
+                VirtualKeyCode::A,
+                ModifiersState::CTRL | ModifiersState::ALT
+            )
+            .as_deref(),
+            Some("\x1b\x01")
+        );
+    }
+
+    #[test]
+    fn test_encode_char() {
+        assert_eq!(
+            encode_char('é', ModifiersState::empty()).as_deref(),
+            Some("é")
+        );
+        assert_eq!(
+            encode_char('x', ModifiersState::ALT).as_deref(),
+            Some("\x1bx")
+        );
+        assert_eq!(encode_char('\r', ModifiersState::empty()), None);
+    }
+
+    #[test]
+    fn test_application_modes() {
+        let modes = Modes {
+            application_cursor: true,
+            application_keypad: true,
+            ..Modes::default()
+        };
+        let none = ModifiersState::empty();
+
+        assert_eq!(
+            super::encode(VirtualKeyCode::Up, none, &modes).as_deref(),
+            Some("\x1bOA")
+        );
+        assert_eq!(
+            super::encode(VirtualKeyCode::Up, ModifiersState::SHIFT, &modes).as_deref(),
+            Some("\x1b[1;2A")
+        );
+        assert_eq!(
+            super::encode(VirtualKeyCode::Numpad5, none, &modes).as_deref(),
+            Some("\x1bOu")
+        );
+        assert_eq!(
+            super::encode(VirtualKeyCode::NumpadEnter, none, &modes).as_deref(),
+            Some("\x1bOM")
+        );
+        assert_eq!(encode(VirtualKeyCode::Numpad5, none), None);
+    }
+
+    #[test]
+    fn test_encode_kitty() {
+        let event = |key, modifiers, kind, text| KeyEvent {
+            key: Some(key),
+            modifiers,
+            kind,
+            text,
+        };
+        let none = ModifiersState::empty();
+        let press = KeyEventKind::Press;
+        let modes = Modes::default();
+
+        // Ctrl+I and Tab are told apart
+        let ctrl_i = event(VirtualKeyCode::I, ModifiersState::CTRL, press, None);
+        assert_eq!(
+            encode_kitty(&ctrl_i, DISAMBIGUATE, &modes).as_deref(),
+            Some("\x1b[105;5u")
+        );
+        let tab = event(VirtualKeyCode::Tab, none, press, None);
+        assert_eq!(
+            encode_kitty(&tab, DISAMBIGUATE, &modes).as_deref(),
+            Some("\t")
+        );
+
+        let escape = event(VirtualKeyCode::Escape, none, press, None);
+        assert_eq!(
+            encode_kitty(&escape, DISAMBIGUATE, &modes).as_deref(),
+            Some("\x1b[27u")
+        );
+
+        let a = event(VirtualKeyCode::A, none, press, Some('a'));
+        assert_eq!(encode_kitty(&a, DISAMBIGUATE, &modes).as_deref(), Some("a"));
+
+        let up = event(VirtualKeyCode::Up, none, press, None);
+        assert_eq!(
+            encode_kitty(&up, DISAMBIGUATE, &modes).as_deref(),
+            Some("\x1b[A")
+        );
+
+        let numpad = event(VirtualKeyCode::Numpad1, none, press, Some('1'));
+        assert_eq!(
+            encode_kitty(&numpad, DISAMBIGUATE, &modes).as_deref(),
+            Some("\x1b[57400u")
+        );
+
+        // keys that aren't escaped, like all of them without disambiguation, still follow the
+        // application modes
+        let modes = Modes {
+            application_cursor: true,
+            application_keypad: true,
+            ..Modes::default()
+        };
+        assert_eq!(
+            encode_kitty(&up, REPORT_EVENT_TYPES, &modes).as_deref(),
+            Some("\x1bOA")
+        );
+        let enter = event(VirtualKeyCode::NumpadEnter, none, press, None);
+        assert_eq!(
+            encode_kitty(&enter, REPORT_EVENT_TYPES, &modes).as_deref(),
+            Some("\x1bOM")
+        );
+    }
+
+    #[test]
+    fn test_encode_kitty_events() {
+        let flags = DISAMBIGUATE | REPORT_EVENT_TYPES | REPORT_ALTERNATE_KEYS | REPORT_ALL_KEYS;
+        let event = |kind, text| KeyEvent {
+            key: Some(VirtualKeyCode::A),
+            modifiers: ModifiersState::SHIFT,
+            kind,
+            text,
+        };
+        let encode_kitty = |event, flags| super::encode_kitty(&event, flags, &Modes::default());
+
+        assert_eq!(
+            encode_kitty(event(KeyEventKind::Press, Some('A')), flags).as_deref(),
+            Some("\x1b[97:65;2u")
+        );
+        assert_eq!(
+            encode_kitty(event(KeyEventKind::Repeat, Some('A')), flags | REPORT_TEXT).as_deref(),
+            Some("\x1b[97:65;2:2;65u")
+        );
+        assert_eq!(
+            encode_kitty(event(KeyEventKind::Release, None), flags).as_deref(),
+            Some("\x1b[97;2:3u")
+        );
+        assert_eq!(
+            encode_kitty(event(KeyEventKind::Release, None), DISAMBIGUATE),
+            None
+        );
+    }
+
+    #[test]
+    fn test_modify_other_keys() {
+        let encode = |key, modifiers, level| {
+            let modes = Modes {
+                modify_other_keys: level,
+                ..Modes::default()
+            };
+            super::encode(key, modifiers, &modes)
+        };
+        let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+
+        assert_eq!(
+            encode(VirtualKeyCode::A, ctrl_shift, 1).as_deref(),
+            Some("\x1b[27;6;65~")
+        );
+        assert_eq!(
+            encode(VirtualKeyCode::Key1, ModifiersState::CTRL, 1).as_deref(),
+            Some("\x1b[27;5;49~")
+        );
+        assert_eq!(
+            encode(VirtualKeyCode::Return, ModifiersState::CTRL, 1).as_deref(),
+            Some("\x1b[27;5;13~")
+        );
+        assert_eq!(
+            encode(VirtualKeyCode::A, ModifiersState::CTRL, 1).as_deref(),
+            Some("\x01")
+        );
+        assert_eq!(
+            encode(VirtualKeyCode::A, ModifiersState::CTRL, 2).as_deref(),
+            Some("\x1b[27;5;97~")
+        );
+        assert_eq!(encode(VirtualKeyCode::A, ModifiersState::SHIFT, 2), None);
+        assert_eq!(
+            encode(VirtualKeyCode::Up, ModifiersState::CTRL, 2).as_deref(),
+            Some("\x1b[1;5A")
+        );
+
+        // the code is the character the layout produced, Ctrl+Shift+1 types `!`
+        let typed = |key, text| {
+            let event = KeyEvent {
+                key: Some(key),
+                modifiers: ctrl_shift,
+                kind: KeyEventKind::Press,
+                text,
+            };
+            let modes = Modes {
+                modify_other_keys: 1,
+                ..Modes::default()
+            };
+            encode_typed(&event, &modes)
+        };
+        assert_eq!(
+            typed(VirtualKeyCode::Key1, Some('!')).as_deref(),
+            Some("\x1b[27;6;33~")
+        );
+        assert_eq!(
+            typed(VirtualKeyCode::Key7, Some('/')).as_deref(),
+            Some("\x1b[27;6;47~")
+        );
+        assert_eq!(
+            typed(VirtualKeyCode::A, None).as_deref(),
+            Some("\x1b[27;6;65~")
+        );
+    }
+}
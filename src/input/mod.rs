@@ -0,0 +1,5 @@
+// turns window input into the bytes programs expect, depending on the modes they set
+pub mod keyboard;
+pub mod mouse;
+pub mod paste;
+pub mod unicode;
@@ -0,0 +1,222 @@
+// encodes mouse events for programs that turned on mouse reporting
+//
+// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-Mouse-Tracking
+use winit::event::ModifiersState;
+
+// which events are reported, picked with DECSET 9, 1000, 1002 and 1003
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MouseMode {
+    #[default]
+    Off,
+    // presses only, without modifiers
+    X10,
+    // presses and releases
+    Press,
+    // also motion while a button is held
+    Drag,
+    // also motion without a button held
+    Motion,
+}
+
+// how events are written, picked with DECSET 1005, 1006 and 1015
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MouseEncoding {
+    // `CSI M` followed by three bytes, each offset by 32
+    #[default]
+    Default,
+    // like the default, but coordinates past 95 are written as UTF-8
+    Utf8,
+    // `CSI < b ; x ; y M` for presses and `m` for releases
+    Sgr,
+    // `CSI b ; x ; y M`
+    Urxvt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl MouseButton {
+    fn code(self) -> u32 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::WheelUp => 64,
+            MouseButton::WheelDown => 65,
+            MouseButton::WheelLeft => 66,
+            MouseButton::WheelRight => 67,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseEvent {
+    Press(MouseButton),
+    Release(MouseButton),
+    // motion to another cell, with the button held down if there is one
+    Motion(Option<MouseButton>),
+}
+
+// what a program is sent for `event` at the 0 based `cell`, None when `mode` doesn't report it
+// or the position can't be encoded
+pub fn encode(
+    mode: MouseMode,
+    encoding: MouseEncoding,
+    event: MouseEvent,
+    modifiers: ModifiersState,
+    cell: (u32, u32),
+) -> Option<String> {
+    let reported = match (mode, event) {
+        (MouseMode::Off, _) => false,
+        (MouseMode::X10, MouseEvent::Press(_)) => true,
+        (MouseMode::X10, _) => false,
+        (_, MouseEvent::Press(_) | MouseEvent::Release(_)) => true,
+        (MouseMode::Drag, MouseEvent::Motion(button)) => button.is_some(),
+        (MouseMode::Motion, MouseEvent::Motion(_)) => true,
+        (MouseMode::Press, MouseEvent::Motion(_)) => false,
+    };
+
+    // wheels have no release
+    if !reported
+        || matches!(
+            event,
+            MouseEvent::Release(
+                MouseButton::WheelUp
+                    | MouseButton::WheelDown
+                    | MouseButton::WheelLeft
+                    | MouseButton::WheelRight
+            )
+        )
+    {
+        return None;
+    }
+
+    let mut code = match event {
+        MouseEvent::Press(button) => button.code(),
+        // only SGR says which button was released
+        MouseEvent::Release(button) if encoding == MouseEncoding::Sgr => button.code(),
+        MouseEvent::Release(_) => 3,
+        MouseEvent::Motion(button) => 32 + button.map_or(3, MouseButton::code),
+    };
+
+    if mode != MouseMode::X10 {
+        if modifiers.shift() {
+            code += 4;
+        }
+        if modifiers.alt() {
+            code += 8;
+        }
+        if modifiers.ctrl() {
+            code += 16;
+        }
+    }
+
+    let (x, y) = (cell.0 + 1, cell.1 + 1);
+
+    match encoding {
+        MouseEncoding::Sgr => {
+            let last = if matches!(event, MouseEvent::Release(_)) {
+                'm'
+            } else {
+                'M'
+            };
+            Some(format!("\x1b[<{};{};{}{}", code, x, y, last))
+        }
+        MouseEncoding::Urxvt => Some(format!("\x1b[{};{};{}M", 32 + code, x, y)),
+        MouseEncoding::Default => {
+            // the bytes past 127 xterm allows here aren't valid UTF-8, so they can't be sent
+            let byte = |n: u32| char::from_u32(32 + n).filter(|c| c.is_ascii());
+            Some(format!("\x1b[M{}{}{}", byte(code)?, byte(x)?, byte(y)?))
+        }
+        MouseEncoding::Utf8 => {
+            let character = |n: u32| char::from_u32(32 + n).filter(|_| n <= 2015);
+            Some(format!(
+                "\x1b[M{}{}{}",
+                character(code)?,
+                character(x)?,
+                character(y)?
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_sgr() {
+        let press = encode(
+            MouseMode::Press,
+            MouseEncoding::Sgr,
+            MouseEvent::Press(MouseButton::Left),
+            ModifiersState::CTRL,
+            (4, 9),
+        );
+        assert_eq!(press.as_deref(), Some("\x1b[<16;5;10M"));
+
+        let release = encode(
+            MouseMode::Press,
+            MouseEncoding::Sgr,
+            MouseEvent::Release(MouseButton::Right),
+            ModifiersState::empty(),
+            (0, 0),
+        );
+        assert_eq!(release.as_deref(), Some("\x1b[<2;1;1m"));
+    }
+
+    #[test]
+    fn test_encode_modes() {
+        let motion = |mode, button| {
+            encode(
+                mode,
+                MouseEncoding::Default,
+                MouseEvent::Motion(button),
+                ModifiersState::empty(),
+                (0, 0),
+            )
+        };
+
+        assert_eq!(motion(MouseMode::Press, Some(MouseButton::Left)), None);
+        assert_eq!(motion(MouseMode::Drag, None), None);
+        assert_eq!(
+            motion(MouseMode::Drag, Some(MouseButton::Left)).as_deref(),
+            Some("\x1b[M@!!")
+        );
+        assert_eq!(
+            motion(MouseMode::Motion, None).as_deref(),
+            Some("\x1b[MC!!")
+        );
+    }
+
+    #[test]
+    fn test_encode_formats() {
+        let wheel = |encoding, cell| {
+            encode(
+                MouseMode::Press,
+                encoding,
+                MouseEvent::Press(MouseButton::WheelUp),
+                ModifiersState::SHIFT,
+                cell,
+            )
+        };
+
+        assert_eq!(
+            wheel(MouseEncoding::Urxvt, (199, 0)).as_deref(),
+            Some("\x1b[100;200;1M")
+        );
+        assert_eq!(wheel(MouseEncoding::Default, (199, 0)), None);
+        assert_eq!(
+            wheel(MouseEncoding::Utf8, (199, 0)).as_deref(),
+            Some("\x1b[Md\u{e8}!")
+        );
+    }
+}
@@ -0,0 +1,32 @@
+// prepares pasted text for the program, bracketing it when the program asked with mode 2004
+
+const START: &str = "\x1b[200~";
+const END: &str = "\x1b[201~";
+
+// what is sent for pasting `text`. the end marker is stripped from bracketed pastes so the text
+// can't end the paste early and have the rest run as typed input
+pub fn encode(text: &str, bracketed: bool) -> String {
+    if !bracketed {
+        return text.to_string();
+    }
+
+    let mut text = text.to_string();
+    // stripping one marker can join the pieces of another
+    while text.contains(END) {
+        text = text.replace(END, "");
+    }
+
+    format!("{}{}{}", START, text, END)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("ls\nrm -rf /\n", false), "ls\nrm -rf /\n");
+        assert_eq!(encode("ls\n", true), "\x1b[200~ls\n\x1b[201~");
+        assert_eq!(encode("a\x1b[20\x1b[201~1~b", true), "\x1b[200~ab\x1b[201~");
+    }
+}
@@ -0,0 +1,220 @@
+// picks a character to type by its codepoint, Unicode name or emoji short name
+use std::{cmp::Reverse, sync::OnceLock};
+
+// how many candidates a search keeps
+const MAX_CANDIDATES: usize = 32;
+
+// matches past this are dropped before ranking, names are searched in codepoint order so the
+// common characters come first
+const MAX_MATCHES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub text: String,
+    pub name: String,
+}
+
+impl Candidate {
+    fn character(c: char) -> Self {
+        let name = unicode_names2::name(c).map_or_else(String::new, |name| name.to_string());
+        Self {
+            text: c.to_string(),
+            name,
+        }
+    }
+}
+
+// the characters with a name, built on the first search. ideographs and syllables are named
+// after their codepoint, so they're left out and found by it instead
+fn names() -> &'static [(char, String)] {
+    static NAMES: OnceLock<Vec<(char, String)>> = OnceLock::new();
+
+    NAMES.get_or_init(|| {
+        (0x20..0x20000)
+            .filter(|c| {
+                !matches!(
+                    c,
+                    0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xac00..=0xd7a3 | 0x17000..=0x18cff
+                )
+            })
+            .filter_map(char::from_u32)
+            .filter_map(|c| Some((c, unicode_names2::name(c)?.to_string())))
+            .collect()
+    })
+}
+
+// the codepoint `query` spells in hex, with or without a `U+` in front
+fn codepoint(query: &str) -> Option<char> {
+    let hex = query
+        .strip_prefix("U+")
+        .or_else(|| query.strip_prefix("u+"))
+        .unwrap_or(query);
+
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .filter(|c| !c.is_control())
+}
+
+// characters and emoji matching `query`, best first
+pub fn search(query: &str) -> Vec<Candidate> {
+    let query = query.trim();
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let words = query
+        .split_whitespace()
+        .map(str::to_uppercase)
+        .collect::<Vec<_>>();
+    let upper = words.join(" ");
+    let lower = query.to_lowercase();
+
+    // exact names first, then names starting with the query
+    let rank = |name: &str| {
+        let name = name.to_uppercase();
+        if name == upper {
+            2
+        } else if name.starts_with(&upper) {
+            1
+        } else {
+            0
+        }
+    };
+
+    let mut matches = vec![];
+
+    for (c, name) in names() {
+        if matches.len() >= MAX_MATCHES {
+            break;
+        }
+        if words.iter().all(|word| name.contains(word.as_str())) {
+            matches.push((rank(name), Candidate::character(*c)));
+        }
+    }
+
+    for emoji in emojis::iter() {
+        let shortcode = emoji.shortcodes().find(|code| code.contains(&lower));
+        let name = emoji.name().to_uppercase();
+        if shortcode.is_none() && !words.iter().all(|word| name.contains(word.as_str())) {
+            continue;
+        }
+        if matches.iter().any(|(_, c)| c.text == emoji.as_str()) {
+            continue;
+        }
+
+        let rank = match shortcode {
+            Some(code) if code == lower => 2,
+            Some(code) if code.starts_with(&lower) => 1,
+            Some(_) => 0,
+            None => rank(emoji.name()),
+        };
+        let name = match shortcode.or_else(|| emoji.shortcode()) {
+            Some(code) => format!(":{}: {}", code, emoji.name()),
+            None => emoji.name().to_string(),
+        };
+        matches.push((
+            rank,
+            Candidate {
+                text: emoji.as_str().to_string(),
+                name,
+            },
+        ));
+    }
+
+    matches.sort_by_key(|(rank, _)| Reverse(*rank));
+
+    // words like "face" are hex too, so the codepoint only comes first when the query looks like
+    // one
+    let codepoint = codepoint(query).map(Candidate::character);
+    let looks_like_codepoint =
+        query.to_uppercase().starts_with("U+") || query.chars().any(|c| c.is_ascii_digit());
+
+    let mut candidates = vec![];
+    let ranked = matches.into_iter().map(|(_, candidate)| candidate);
+    let all = if looks_like_codepoint {
+        codepoint.into_iter().chain(ranked).collect::<Vec<_>>()
+    } else {
+        ranked.chain(codepoint).collect()
+    };
+    for candidate in all {
+        if candidates.len() >= MAX_CANDIDATES {
+            break;
+        }
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+
+    candidates
+}
+
+// the state of the overlay while it's open
+#[derive(Debug, Default)]
+pub struct UnicodeInput {
+    pub query: String,
+    pub candidates: Vec<Candidate>,
+    pub selected: usize,
+}
+
+impl UnicodeInput {
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update();
+    }
+
+    fn update(&mut self) {
+        self.candidates = search(&self.query);
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.candidates.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn chosen(&self) -> Option<&str> {
+        self.candidates
+            .get(self.selected)
+            .map(|candidate| candidate.text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        assert_eq!(search("2192")[0].text, "→");
+        assert_eq!(search("U+3b1")[0].text, "α");
+        assert_eq!(search("rightwards arrow")[0].text, "→");
+        assert_eq!(search("thumbsup")[0].text, "👍");
+        assert_ne!(search("face")[0].text, "\u{face}");
+        assert!(search("").is_empty());
+    }
+
+    #[test]
+    fn test_unicode_input() {
+        let mut input = UnicodeInput::default();
+        for c in "greek small letter alpha".chars() {
+            input.push(c);
+        }
+        assert_eq!(input.chosen(), Some("α"));
+
+        input.select_next();
+        assert_eq!(input.selected, 1);
+        input.pop();
+        assert_eq!(input.selected, 0);
+    }
+}
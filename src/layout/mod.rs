@@ -1,20 +1,205 @@
-use std::ops::{Index, IndexMut};
+use std::{
+    collections::VecDeque,
+    ops::{Index, IndexMut},
+};
 
 // this is the way the text is stored
 use wgpu_glyph::{ab_glyph::PxScale, Text};
 
+use crate::graphics::Placement;
+
+const SCROLLBACK_LINES: usize = 10_000;
+
+// lines are numbered from the oldest scrollback line, so line `scrollback.len()` is the top of
+// the screen
 #[derive(Debug)]
 pub struct Grid {
-    rows: Vec<Row<AnsiChar>>,
+    rows: VecDeque<Row<AnsiChar>>,
+    scrollback: VecDeque<Row<AnsiChar>>,
+    // how many lines the view is scrolled back from the bottom
+    pub display_offset: usize,
     pub size: (u32, u32),
+    pub placements: Vec<Placement>,
+    // whether rows scrolled off the top are kept, the alternate screen has no scrollback
+    pub history: bool,
+    // the line of the last OSC 133 B mark, which a failed command's exit status is put on
+    command_line: Option<usize>,
 }
 
 impl Grid {
     pub fn new(width: u32, height: u32) -> Self {
         Self {
-            rows: vec![Row::new(vec![AnsiChar::default(); width as usize]); height as usize],
+            rows: vec![Row::new(vec![AnsiChar::default(); width as usize]); height as usize].into(),
+            scrollback: VecDeque::new(),
+            display_offset: 0,
             size: (width, height),
+            placements: vec![],
+            history: true,
+            command_line: None,
+        }
+    }
+
+    // moves every row up by one, pushing the top row into the scrollback
+    pub fn scroll_up(&mut self) {
+        let Some(row) = self.rows.pop_front() else {
+            return;
+        };
+        self.rows
+            .push_back(Row::new(vec![AnsiChar::default(); self.size.0 as usize]));
+
+        if !self.history {
+            self.shift_lines(self.scrollback.len());
+            return;
+        }
+
+        if self.scrollback.len() == SCROLLBACK_LINES {
+            self.scrollback.pop_front();
+
+            // every line number shifts down by one
+            self.shift_lines(0);
+        } else if self.display_offset > 0 {
+            // keep the view still while scrolled back
+            self.display_offset += 1;
+        }
+        self.scrollback.push_back(row);
+    }
+
+    // moves what's on the lines after `removed` up by one, after that line was dropped
+    fn shift_lines(&mut self, removed: usize) {
+        self.placements.retain(|p| p.line > removed);
+        for placement in self.placements.iter_mut() {
+            placement.line -= 1;
+        }
+
+        self.command_line = match self.command_line {
+            Some(line) if line > removed => Some(line - 1),
+            _ => None,
+        };
+    }
+
+    pub fn total_lines(&self) -> usize {
+        self.scrollback.len() + self.rows.len()
+    }
+
+    pub fn line(&self, line: usize) -> &Row<AnsiChar> {
+        if line < self.scrollback.len() {
+            &self.scrollback[line]
+        } else {
+            &self.rows[line - self.scrollback.len()]
+        }
+    }
+
+    pub fn line_mut(&mut self, line: usize) -> &mut Row<AnsiChar> {
+        if line < self.scrollback.len() {
+            &mut self.scrollback[line]
+        } else {
+            let screen_line = line - self.scrollback.len();
+            &mut self.rows[screen_line]
+        }
+    }
+
+    // the line number of a row on the screen
+    pub fn screen_line(&self, row: u32) -> usize {
+        self.scrollback.len() + row as usize
+    }
+
+    // the line shown at the top of the screen
+    pub fn top_line(&self) -> usize {
+        self.scrollback.len() - self.display_offset
+    }
+
+    // the row shown at `row` on the screen, taking the display offset into account
+    pub fn display_row(&self, row: usize) -> &Row<AnsiChar> {
+        self.line(self.top_line() + row)
+    }
+
+    // scrolls the view back by `lines`, or forward when negative
+    pub fn scroll_display(&mut self, lines: isize) {
+        self.display_offset = self
+            .display_offset
+            .saturating_add_signed(lines)
+            .min(self.scrollback.len());
+    }
+
+    // scrolls the view so that `line` is at the top of the screen
+    pub fn scroll_to_line(&mut self, line: usize) {
+        self.display_offset = self.scrollback.len().saturating_sub(line);
+    }
+
+    // places an OSC 133 mark, a failed command's exit status also goes on the line it started on
+    pub fn mark(&mut self, line: usize, column: u32, mark: PromptMark) {
+        match mark {
+            PromptMark::CommandStart => self.command_line = Some(line),
+            PromptMark::CommandEnd(Some(code)) if code != 0 => {
+                if let Some(start) = self.command_line.filter(|&l| l < self.total_lines()) {
+                    self.line_mut(start).metadata.exit_status = Some(code);
+                }
+            }
+            _ => {}
         }
+
+        self.line_mut(line).metadata.marks.push((column, mark));
+    }
+
+    pub fn lines_with_mark(&self, mark: PromptMark) -> Vec<usize> {
+        (0..self.total_lines())
+            .filter(|&line| self.line(line).metadata.has_mark(mark))
+            .collect()
+    }
+
+    // text between two positions, given as (column, line), with trailing blanks trimmed
+    pub fn text_between(&self, start: (u32, usize), end: (u32, usize)) -> String {
+        let mut text = String::new();
+
+        for line in start.1..=end.1.min(self.total_lines() - 1) {
+            let row = self.line(line);
+            let from = if line == start.1 { start.0 } else { 0 };
+            let to = if line == end.1 { end.0 } else { row.length };
+
+            let s = (from..to.min(row.length))
+                .map(|i| row[i as usize].character)
+                .collect::<String>();
+            text.push_str(s.trim_end());
+
+            if line != end.1 {
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+
+    // the number of usable columns on screen row `row`, which double-width rows halve
+    pub fn columns(&self, row: u32) -> u32 {
+        self.rows[row.min(self.size.1 - 1) as usize]
+            .attribute
+            .columns(self.size.0)
+    }
+
+    // fills the screen with `c` and makes every row single width, used by DECALN
+    pub fn fill(&mut self, c: AnsiChar) {
+        for row in self.rows.iter_mut() {
+            row.row.fill(c.clone());
+            row.attribute = LineAttribute::SingleWidth;
+        }
+    }
+
+    // blanks the screen, and the scrollback too when `scrollback` is set
+    pub fn clear(&mut self, scrollback: bool) {
+        self.rows =
+            vec![Row::new(vec![AnsiChar::default(); self.size.0 as usize]); self.size.1 as usize]
+                .into();
+
+        if scrollback {
+            self.scrollback.clear();
+            self.placements.clear();
+            self.command_line = None;
+        } else {
+            let top = self.scrollback.len();
+            self.placements.retain(|p| p.line < top);
+            self.command_line = self.command_line.filter(|&line| line < top);
+        }
+        self.display_offset = 0;
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -44,10 +229,69 @@ impl IndexMut<usize> for Grid {
     }
 }
 
+// shell integration marks reported through OSC 133
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptMark {
+    PromptStart,
+    CommandStart,
+    OutputStart,
+    CommandEnd(Option<i32>),
+}
+
+impl PromptMark {
+    fn same_kind(&self, other: &PromptMark) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RowMetadata {
+    // marks placed on this row along with the column they were placed at
+    pub marks: Vec<(u32, PromptMark)>,
+    // set on the row a command was started on once that command fails
+    pub exit_status: Option<i32>,
+}
+
+impl RowMetadata {
+    pub fn has_mark(&self, mark: PromptMark) -> bool {
+        self.marks.iter().any(|(_, m)| m.same_kind(&mark))
+    }
+
+    pub fn mark_column(&self, mark: PromptMark) -> Option<u32> {
+        self.marks
+            .iter()
+            .find(|(_, m)| m.same_kind(&mark))
+            .map(|(col, _)| *col)
+    }
+}
+
+// set with DECDWL and DECDHL, double-height text takes two rows showing its top and bottom
+// halves
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineAttribute {
+    #[default]
+    SingleWidth,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineAttribute {
+    // how many of `width` columns fit on a row with this attribute
+    pub fn columns(self, width: u32) -> u32 {
+        match self {
+            LineAttribute::SingleWidth => width,
+            _ => (width / 2).max(1),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Row<T> {
     pub length: u32,
     row: Vec<T>,
+    pub metadata: RowMetadata,
+    pub attribute: LineAttribute,
 }
 
 impl<T> Row<T> {
@@ -55,12 +299,10 @@ impl<T> Row<T> {
         Self {
             length: v.len() as u32,
             row: v,
+            metadata: RowMetadata::default(),
+            attribute: LineAttribute::default(),
         }
     }
-
-    pub fn iter(&self) -> std::slice::Iter<T> {
-        self.row.iter()
-    }
 }
 
 impl<T> Index<usize> for Row<T> {
@@ -95,27 +337,11 @@ impl AnsiChar {
         }
     }
 
-    pub fn text<'a>(&'a self, scale: f32) -> Text<'a> {
+    pub fn text(&self, scale: f32) -> Text<'_> {
         Text::new(&self.char_string)
             .with_color(self.foreground)
             .with_scale(PxScale::from(scale))
     }
-
-    pub fn with_fg_color(&mut self, color: [f32; 4]) -> Self {
-        self.foreground = color;
-        self.to_owned()
-    }
-
-    pub fn with_bg_color(&mut self, color: [f32; 4]) -> Self {
-        self.background = color;
-        self.to_owned()
-    }
-
-    pub fn with_char(&mut self, c: char) -> Self {
-        self.character = c;
-        self.char_string = self.character.to_string();
-        self.to_owned()
-    }
 }
 
 impl Default for AnsiChar {
@@ -149,7 +375,34 @@ impl Layout {
 
     pub fn calculate(&self) -> (u32, u32) {
         let text_width = ((self.px_width / self.scale) / (self.font_size / 2.0)) * 0.96;
-        let text_height = (self.px_height as f32 / self.scale) / self.font_size;
+        let text_height = (self.px_height / self.scale) / self.font_size;
         (text_width as u32, text_height as u32)
     }
+
+    // the cell under a pixel position, clamped to the `width` by `height` grid. the text starts
+    // after the same gutter the renderer leaves
+    pub fn cell_at(&self, x: f64, y: f64, width: u32, height: u32) -> (u32, u32) {
+        let (cell_width, cell_height) = self.cell_size();
+        let col = ((x as f32 - self.font_size / 4.0) / cell_width).max(0.0) as u32;
+        let row = (y as f32 / cell_height).max(0.0) as u32;
+
+        (col.min(width - 1), row.min(height - 1))
+    }
+
+    // the pixel position of the top left corner of a cell, the inverse of `cell_at`
+    pub fn cell_position(&self, col: u32, row: u32) -> (f32, f32) {
+        let (cell_width, cell_height) = self.cell_size();
+        (
+            self.font_size / 4.0 + col as f32 * cell_width,
+            row as f32 * cell_height,
+        )
+    }
+
+    // the size of a cell in pixels, matching `calculate`
+    pub fn cell_size(&self) -> (f32, f32) {
+        (
+            self.scale * self.font_size / 2.0 / 0.96,
+            self.scale * self.font_size,
+        )
+    }
 }
@@ -1,14 +1,17 @@
 // runs the program and for now handles event loop, later event loop will be in screen
 use winit::{
     dpi::PhysicalSize,
-    event::{ElementState, Event, KeyboardInput, WindowEvent},
+    event::{Event, WindowEvent},
 };
 
 use crate::config::Config;
 
 mod config;
 mod display;
+mod graphics;
+mod input;
 mod layout;
+mod notification;
 mod render;
 mod screen;
 mod terminal;
@@ -23,7 +26,7 @@ pub async fn run() {
         .with_min_inner_size(PhysicalSize::new(50, 20))
         .build(&event_loop)
         .unwrap();
-    let config = Config::default();
+    let config = Config::from_args(std::env::args().skip(1));
     let mut screen = screen::Screen::new(window, config).await;
     screen.color_background();
 
@@ -57,16 +60,37 @@ pub async fn run() {
             WindowEvent::KeyboardInput { input, .. } => {
                 screen.key_pressed(input);
             }
+            WindowEvent::ReceivedCharacter(c) => {
+                screen.received_character(*c);
+            }
+            WindowEvent::Ime(event) => {
+                screen.ime(event);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                screen.mouse_moved(*position);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                screen.mouse_input(*state, *button);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                screen.mouse_wheel(*delta);
+            }
+            WindowEvent::Focused(focused) => {
+                screen.set_focused(*focused);
+            }
             WindowEvent::ModifiersChanged(modifiers) => {
                 screen.terminal.modifiers = *modifiers;
             }
             _ => (),
         },
         Event::RedrawRequested(_) => {
-            screen.render();
+            screen.request_render();
         }
         Event::MainEventsCleared => {
             screen.check_term();
+            if screen.should_close() {
+                *control_flow = winit::event_loop::ControlFlow::Exit;
+            }
         }
         _ => (),
     });
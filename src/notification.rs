@@ -0,0 +1,199 @@
+// desktop notifications requested by programs through OSC 9, OSC 777 and OSC 99
+use std::{collections::HashMap, sync::mpsc::Sender};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use zbus::{blocking::Connection, zvariant::Value};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+impl Notification {
+    pub fn new(title: String, body: String) -> Self {
+        Self { title, body }
+    }
+}
+
+pub trait NotificationSink {
+    fn notify(&mut self, notification: &Notification);
+}
+
+// sends notifications to org.freedesktop.Notifications on the session bus, from a thread of its
+// own so a slow notification daemon doesn't hold up the window
+pub struct FreedesktopSink {
+    sender: Sender<Notification>,
+}
+
+impl Default for FreedesktopSink {
+    fn default() -> Self {
+        let (sender, reciever) = std::sync::mpsc::channel::<Notification>();
+
+        std::thread::spawn(move || {
+            let mut connection = None;
+
+            for notification in reciever {
+                // connected on the first notification, and again after failing
+                if connection.is_none() {
+                    connection = match Connection::session() {
+                        Ok(connection) => Some(connection),
+                        Err(e) => {
+                            println!("unable to connect to the session bus: {}", e);
+                            continue;
+                        }
+                    };
+                }
+
+                if let Some(bus) = &connection {
+                    if let Err(e) = send_notification(bus, &notification) {
+                        println!("unable to send notification: {}", e);
+                        connection = None;
+                    }
+                }
+            }
+        });
+
+        Self { sender }
+    }
+}
+
+impl NotificationSink for FreedesktopSink {
+    fn notify(&mut self, notification: &Notification) {
+        let _ = self.sender.send(notification.clone());
+    }
+}
+
+fn send_notification(connection: &Connection, notification: &Notification) -> zbus::Result<()> {
+    // OSC 9 only carries a message, which reads best as the summary
+    let (summary, body) = if notification.title.is_empty() {
+        (notification.body.as_str(), "")
+    } else {
+        (notification.title.as_str(), notification.body.as_str())
+    };
+
+    let actions: Vec<&str> = vec![];
+    let hints: HashMap<&str, Value<'_>> = HashMap::new();
+
+    connection.call_method(
+        Some("org.freedesktop.Notifications"),
+        "/org/freedesktop/Notifications",
+        Some("org.freedesktop.Notifications"),
+        "Notify",
+        &(
+            "Nyat",
+            0u32,
+            "utilities-terminal",
+            summary,
+            body,
+            actions,
+            hints,
+            -1i32,
+        ),
+    )?;
+
+    Ok(())
+}
+
+// keeps every notification it receives, for tests
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    pub notifications: Vec<Notification>,
+}
+
+#[cfg(test)]
+impl NotificationSink for MemorySink {
+    fn notify(&mut self, notification: &Notification) {
+        self.notifications.push(notification.clone());
+    }
+}
+
+// hands pending notifications to the sink, dropping them if the window is focused and only
+// unfocused notifications are wanted
+pub fn deliver(
+    notifications: Vec<Notification>,
+    sink: &mut dyn NotificationSink,
+    focused: bool,
+    only_when_unfocused: bool,
+) {
+    if focused && only_when_unfocused {
+        return;
+    }
+
+    for notification in notifications.iter() {
+        sink.notify(notification);
+    }
+}
+
+// applies one OSC 99 chunk to the notification being built, returning it once it is done
+//
+// the metadata is a `:` separated list of `key=value` pairs, of which `d` (done), `e` (base64
+// payload) and `p` (which part the payload is) are used
+pub fn kitty_chunk(
+    pending: &mut Option<Notification>,
+    metadata: &str,
+    payload: &str,
+) -> Option<Notification> {
+    let mut done = true;
+    let mut encoded = false;
+    let mut part = "title";
+
+    for pair in metadata.split(':') {
+        match pair.split_once('=') {
+            Some(("d", value)) => done = value != "0",
+            Some(("e", value)) => encoded = value == "1",
+            Some(("p", value)) => part = value,
+            _ => {}
+        }
+    }
+
+    let payload = if encoded {
+        String::from_utf8(STANDARD.decode(payload).ok()?).ok()?
+    } else {
+        payload.to_string()
+    };
+
+    let notification = pending.get_or_insert_with(Notification::default);
+    match part {
+        "title" => notification.title.push_str(&payload),
+        "body" => notification.body.push_str(&payload),
+        _ => {}
+    }
+
+    if done {
+        pending.take()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kitty_chunk() {
+        let mut pending = None;
+
+        assert_eq!(kitty_chunk(&mut pending, "i=1:d=0", "Build"), None);
+        assert_eq!(
+            kitty_chunk(&mut pending, "i=1:p=body:e=1", "ZG9uZQ=="),
+            Some(Notification::new("Build".to_string(), "done".to_string()))
+        );
+        assert_eq!(pending, None);
+    }
+
+    #[test]
+    fn test_deliver() {
+        let notification = Notification::new("Build".to_string(), "done".to_string());
+        let mut sink = MemorySink::default();
+
+        deliver(vec![notification.clone()], &mut sink, true, true);
+        assert!(sink.notifications.is_empty());
+
+        deliver(vec![notification.clone()], &mut sink, false, true);
+        deliver(vec![notification.clone()], &mut sink, true, false);
+        assert_eq!(sink.notifications, vec![notification.clone(), notification]);
+    }
+}
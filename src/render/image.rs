@@ -0,0 +1,299 @@
+// draws images from the terminal's image store as textured quads
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::graphics::Image;
+
+const SHADER: &str = r#"
+@group(0) @binding(0)
+var image: texture_2d<f32>;
+@group(0) @binding(1)
+var image_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) uv: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(image, image_sampler, in.uv);
+}
+"#;
+
+// two floats of position followed by two floats of texture coordinates
+const VERTEX_SIZE: u64 = 4 * 4;
+
+struct Texture {
+    // kept alive for as long as the bind group uses it
+    _texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    // the generation of the image it was made from, an image stored under the same id replaces it
+    generation: u64,
+}
+
+pub struct ImageRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: HashMap<u32, Texture>,
+    queued: Vec<Queued>,
+}
+
+struct Queued {
+    id: u32,
+    // x, y, width and height in pixels
+    rect: [f32; 4],
+    // the left, top, right and bottom texture coordinates to show
+    uv: [f32; 4],
+    z_index: i32,
+}
+
+impl ImageRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Image Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Image Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Image Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Image Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+                }],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Image Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            textures: HashMap::new(),
+            queued: vec![],
+        }
+    }
+
+    pub fn is_uploaded(&self, id: u32, generation: u64) -> bool {
+        self.textures
+            .get(&id)
+            .is_some_and(|texture| texture.generation == generation)
+    }
+
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id: u32,
+        generation: u64,
+        image: &Image,
+    ) {
+        // the texture of an earlier image with this id isn't shown even if this one can't be
+        self.textures.remove(&id);
+
+        let max = device.limits().max_texture_dimension_2d;
+        if image.width == 0 || image.height == 0 || image.width > max || image.height > max {
+            return;
+        }
+
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Image Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &image.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * image.width),
+                rows_per_image: Some(image.height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Image Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        self.textures.insert(
+            id,
+            Texture {
+                _texture: texture,
+                bind_group,
+                generation,
+            },
+        );
+    }
+
+    // drops the textures of images that are no longer stored
+    pub fn retain(&mut self, ids: &[u32]) {
+        self.textures.retain(|id, _| ids.contains(id));
+    }
+
+    pub fn has_queued(&self, above_text: bool) -> bool {
+        self.queued.iter().any(|q| (q.z_index >= 0) == above_text)
+    }
+
+    pub fn queue(&mut self, id: u32, rect: [f32; 4], uv: [f32; 4], z_index: i32) {
+        self.queued.push(Queued {
+            id,
+            rect,
+            uv,
+            z_index,
+        });
+    }
+
+    // draws and clears the queued images of one layer in z-index order, images with a negative
+    // z-index go beneath the text and the rest above it. positions are in pixels of a `width` by
+    // `height` surface
+    pub fn draw_queued<'a>(
+        &'a mut self,
+        device: &wgpu::Device,
+        pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a mut Option<wgpu::Buffer>,
+        width: u32,
+        height: u32,
+        above_text: bool,
+    ) {
+        let (mut queued, rest): (Vec<Queued>, Vec<Queued>) = std::mem::take(&mut self.queued)
+            .into_iter()
+            .partition(|q| (q.z_index >= 0) == above_text);
+        self.queued = rest;
+
+        queued.retain(|q| self.textures.contains_key(&q.id));
+        queued.sort_by_key(|q| q.z_index);
+
+        if queued.is_empty() {
+            return;
+        }
+
+        let mut vertices: Vec<f32> = Vec::with_capacity(queued.len() * 6 * 4);
+        for q in queued.iter() {
+            let [x, y, w, h] = q.rect;
+            let [u0, v0, u1, v1] = q.uv;
+
+            let left = x / width as f32 * 2.0 - 1.0;
+            let right = (x + w) / width as f32 * 2.0 - 1.0;
+            let top = 1.0 - y / height as f32 * 2.0;
+            let bottom = 1.0 - (y + h) / height as f32 * 2.0;
+
+            vertices.extend_from_slice(&[
+                left, top, u0, v0, left, bottom, u0, v1, right, bottom, u1, v1, left, top, u0, v0,
+                right, bottom, u1, v1, right, top, u1, v0,
+            ]);
+        }
+
+        let bytes = vertices
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect::<Vec<u8>>();
+
+        *buffer = Some(
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Image Vertex Buffer"),
+                contents: &bytes,
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+        );
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, buffer.as_ref().unwrap().slice(..));
+        for (i, q) in queued.iter().enumerate() {
+            pass.set_bind_group(0, &self.textures[&q.id].bind_group, &[]);
+            let first = i as u32 * 6;
+            pass.draw(first..first + 6, 0..1);
+        }
+    }
+}
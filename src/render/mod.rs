@@ -1,20 +1,45 @@
 use wgpu::Color;
 // renderer has a height, width, and scale. It uses these values to render text to screen
-use wgpu_glyph::{ab_glyph::FontArc, Section, Text};
+use wgpu_glyph::{
+    ab_glyph::{FontArc, PxScale},
+    GlyphCruncher, OwnedSection, Region, Section, Text,
+};
 
-use crate::{layout::Grid, terminal::Terminal};
+use crate::{
+    input::unicode::UnicodeInput,
+    layout::{AnsiChar, LineAttribute},
+    terminal::{CursorShape, Progress, ProgressState, Terminal},
+};
 
+use self::{
+    image::ImageRenderer,
+    rect::{Rect, RectRenderer},
+};
+
+mod image;
+mod rect;
 mod state;
 
+const PROGRESS_HEIGHT: f32 = 3.0;
+
+// how many candidates the unicode input overlay lists at once
+const UNICODE_INPUT_ROWS: usize = 8;
+
 pub struct Renderer {
     state: state::State,
     brush: wgpu_glyph::GlyphBrush<()>,
-    scale: f32,
+    rects: RectRenderer,
+    images: ImageRenderer,
+    background: Color,
+    // text drawn cut off to a region, like the halves of double-height rows
+    clipped: Vec<(Region, OwnedSection)>,
+    // rows an overlay is drawn over, which `draw_text` leaves empty for this frame
+    covered_rows: std::ops::Range<usize>,
     font_size: f32,
 }
 
 impl Renderer {
-    pub async fn new(window: winit::window::Window, scale: f32, font_size: f32) -> Self {
+    pub async fn new(window: winit::window::Window, font_size: f32) -> Self {
         let state = state::State::new(window).await;
 
         let font =
@@ -23,16 +48,23 @@ impl Renderer {
         let brush = wgpu_glyph::GlyphBrushBuilder::using_font(font)
             .build(&state.device, state.config.format);
 
+        let rects = RectRenderer::new(&state.device, state.config.format);
+        let images = ImageRenderer::new(&state.device, state.config.format);
+
         Self {
             state,
             brush,
-            scale,
+            rects,
+            images,
+            background: Color::BLACK,
+            clipped: vec![],
+            covered_rows: 0..0,
             font_size,
         }
     }
 
     pub fn window(&self) -> &winit::window::Window {
-        &self.state.window()
+        self.state.window()
     }
 
     fn render_full(&mut self, load_op: wgpu::LoadOp<Color>) {
@@ -53,18 +85,40 @@ impl Renderer {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Background Color Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: load_op,
-                    store: true,
-                },
-            })],
-            depth_stencil_attachment: None,
-        });
+        let mut rect_buffer = None;
+        let mut image_buffer = None;
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Background Color Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: load_op,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            // images with a negative z-index are drawn beneath the text
+            self.images.draw_queued(
+                &self.state.device,
+                &mut pass,
+                &mut image_buffer,
+                self.state.size.width,
+                self.state.size.height,
+                false,
+            );
+
+            self.rects.draw_queued(
+                &self.state.device,
+                &mut pass,
+                &mut rect_buffer,
+                self.state.size.width,
+                self.state.size.height,
+            );
+        }
 
         self.brush
             .draw_queued(
@@ -77,50 +131,421 @@ impl Renderer {
             )
             .expect("Draw queued");
 
+        let transform =
+            wgpu_glyph::orthographic_projection(self.state.size.width, self.state.size.height);
+        for (region, section) in std::mem::take(&mut self.clipped) {
+            self.brush.queue(section.to_borrowed());
+            self.brush
+                .draw_queued_with_transform_and_scissoring(
+                    &self.state.device,
+                    &mut self.state.staging_belt,
+                    &mut encoder,
+                    view,
+                    transform,
+                    region,
+                )
+                .expect("Draw queued");
+        }
+
+        let mut above_buffer = None;
+        if self.images.has_queued(true) {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Image Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.images.draw_queued(
+                &self.state.device,
+                &mut pass,
+                &mut above_buffer,
+                self.state.size.width,
+                self.state.size.height,
+                true,
+            );
+        }
+
         self.state.staging_belt.finish();
         self.state.queue.submit(Some(encoder.finish()));
         frame.present();
         self.state.staging_belt.recall();
     }
 
+    // redraws everything over the last background color. Loading the last frame instead would
+    // leave a shrinking or finished progress bar on screen, and a new surface texture isn't
+    // promised to hold the last frame anyway
     pub fn render(&mut self) {
-        self.render_full(wgpu::LoadOp::Load);
+        self.render_full(wgpu::LoadOp::Clear(self.background));
     }
 
     pub fn color_background(&mut self, color: wgpu::Color) {
+        self.background = color;
         self.render_full(wgpu::LoadOp::Clear(color));
     }
 
+    // room left of the text for the failed command indicator
+    fn gutter(&self) -> f32 {
+        self.font_size / 4.0
+    }
+
+    // queues the images placed on the visible part of the grid, uploading any new ones
+    pub fn draw_images(&mut self, terminal: &Terminal) {
+        let grid = &terminal.visible_grid;
+        let (cell_width, cell_height) = terminal.layout.cell_size();
+        let gutter = self.gutter();
+
+        let ids = terminal.images.ids().copied().collect::<Vec<u32>>();
+        self.images.retain(&ids);
+
+        for placement in grid.placements.iter() {
+            let y = (placement.line as f32 - grid.top_line() as f32) * cell_height;
+            if y + placement.height < 0.0 || y > self.state.size.height as f32 {
+                continue;
+            }
+
+            let image = match terminal.images.get(placement.image_id) {
+                Some(image) if image.width > 0 && image.height > 0 => image,
+                _ => continue,
+            };
+            let generation = terminal.images.generation(placement.image_id).unwrap_or(0);
+
+            if !self.images.is_uploaded(placement.image_id, generation) {
+                self.images.upload(
+                    &self.state.device,
+                    &self.state.queue,
+                    placement.image_id,
+                    generation,
+                    image,
+                );
+            }
+
+            let uv = match placement.source {
+                Some([x, y, w, h]) => [
+                    x as f32 / image.width as f32,
+                    y as f32 / image.height as f32,
+                    (x + w) as f32 / image.width as f32,
+                    (y + h) as f32 / image.height as f32,
+                ],
+                None => [0.0, 0.0, 1.0, 1.0],
+            };
+
+            self.images.queue(
+                placement.image_id,
+                [
+                    gutter + placement.col as f32 * cell_width + placement.offset.0,
+                    y + placement.offset.1,
+                    placement.width,
+                    placement.height,
+                ],
+                uv,
+                placement.z_index,
+            );
+        }
+    }
+
+    // a thin bar along the top of the window showing OSC 9;4 progress
+    pub fn draw_progress(&mut self, progress: Option<Progress>) {
+        let progress = match progress {
+            Some(progress) => progress,
+            None => return,
+        };
+
+        let width = self.state.size.width as f32;
+        let (fraction, color) = match progress.state {
+            ProgressState::Normal => (progress.percent as f32 / 100.0, [0.0, 0.6, 1.0, 1.0]),
+            ProgressState::Error => (progress.percent as f32 / 100.0, [1.0, 0.0, 0.0, 1.0]),
+            ProgressState::Paused => (progress.percent as f32 / 100.0, [1.0, 1.0, 0.0, 1.0]),
+            ProgressState::Indeterminate => (1.0, [0.0, 0.6, 1.0, 0.4]),
+        };
+
+        self.rects.queue(Rect::new(
+            0.0,
+            0.0,
+            width * fraction.min(1.0),
+            PROGRESS_HEIGHT,
+            color,
+        ));
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.state.resize(new_size);
     }
 
-    pub fn draw_text(&mut self, terminal: &Terminal) {
+    // draws the cursor as a shape beneath the glyph of its cell and returns the color to draw
+    // that glyph in
+    fn draw_cursor(&mut self, terminal: &Terminal, cell: &AnsiChar, focused: bool) -> [f32; 4] {
+        let (cell_width, cell_height) = terminal.layout.cell_size();
         let grid = &terminal.visible_grid;
+        let col = terminal.cursor.0.min(grid.columns(terminal.cursor.1) - 1);
+
+        let cell_width = match grid[terminal.cursor.1 as usize].attribute {
+            LineAttribute::SingleWidth => cell_width,
+            _ => cell_width * 2.0,
+        };
+        let x = self.gutter() + col as f32 * cell_width;
+        let y = terminal.cursor.1 as f32 * cell_height;
+
+        // blank cells have matching colors, so the cursor would vanish against them
+        let color = if cell.foreground == cell.background {
+            [1.0, 1.0, 1.0, 1.0]
+        } else {
+            cell.foreground
+        };
+        let thickness = (cell_height / 10.0).max(1.0).round();
+
+        if !focused {
+            for rect in [
+                Rect::new(x, y, cell_width, 1.0, color),
+                Rect::new(x, y + cell_height - 1.0, cell_width, 1.0, color),
+                Rect::new(x, y, 1.0, cell_height, color),
+                Rect::new(x + cell_width - 1.0, y, 1.0, cell_height, color),
+            ] {
+                self.rects.queue(rect);
+            }
+            return cell.foreground;
+        }
+
+        match terminal.cursor_style.shape {
+            CursorShape::Block => {
+                self.rects
+                    .queue(Rect::new(x, y, cell_width, cell_height, color));
+                let [r, g, b, _] = cell.background;
+                [r, g, b, 1.0]
+            }
+            CursorShape::Underline => {
+                self.rects.queue(Rect::new(
+                    x,
+                    y + cell_height - thickness,
+                    cell_width,
+                    thickness,
+                    color,
+                ));
+                cell.foreground
+            }
+            CursorShape::Bar => {
+                self.rects
+                    .queue(Rect::new(x, y, thickness, cell_height, color));
+                cell.foreground
+            }
+        }
+    }
+
+    // draws the unicode input overlay over the bottom rows of the grid, the query followed by the
+    // candidates around the selected one. call before `draw_text`
+    pub fn draw_unicode_input(&mut self, terminal: &Terminal, input: &UnicodeInput) {
+        let (_, cell_height) = terminal.layout.cell_size();
+        let width = self.state.size.width as f32;
+        let rows = terminal.visible_grid.size.1 as usize;
+        let pallete = &terminal.setting.pallete;
+
+        let shown = input.candidates.len().min(UNICODE_INPUT_ROWS);
+        let top = rows.saturating_sub(shown + 1);
+        let first = input.selected.saturating_sub(shown.saturating_sub(1));
+        self.covered_rows = top..rows;
+
+        self.rects.queue(Rect::new(
+            0.0,
+            top as f32 * cell_height,
+            width,
+            (rows - top) as f32 * cell_height,
+            [0.12, 0.12, 0.12, 1.0],
+        ));
+
+        let mut lines = vec![(format!("unicode: {}_", input.query), pallete.white)];
+        for (i, candidate) in input.candidates.iter().enumerate().skip(first).take(shown) {
+            if i == input.selected {
+                let y = (top + 1 + i - first) as f32 * cell_height;
+                self.rects.queue(Rect::new(
+                    0.0,
+                    y,
+                    width,
+                    cell_height,
+                    [0.25, 0.3, 0.45, 1.0],
+                ));
+            }
+            lines.push((
+                format!("{}  {}", candidate.text, candidate.name),
+                [0.8, 0.8, 0.8, 1.0],
+            ));
+        }
+
+        for (i, (line, color)) in lines.iter().enumerate() {
+            self.brush.queue(Section {
+                screen_position: (self.gutter(), (top + i) as f32 * cell_height),
+                bounds: (width, self.state.size.height as f32),
+                text: vec![Text::new(line)
+                    .with_color(*color)
+                    .with_scale(self.font_size)],
+                layout: wgpu_glyph::Layout::default_single_line(),
+            });
+        }
+    }
+
+    // draws a line of text over the bottom row of the grid. call before `draw_text`
+    pub fn draw_banner(&mut self, terminal: &Terminal, text: &str) {
+        let (_, cell_height) = terminal.layout.cell_size();
+        let width = self.state.size.width as f32;
+        let row = (terminal.visible_grid.size.1 as usize).saturating_sub(1);
+        let y = row as f32 * cell_height;
+        self.covered_rows = row..row + 1;
+
+        self.rects
+            .queue(Rect::new(0.0, y, width, cell_height, [0.4, 0.1, 0.1, 1.0]));
+        self.brush.queue(Section {
+            screen_position: (self.gutter(), y),
+            bounds: (width, self.state.size.height as f32),
+            text: vec![Text::new(text)
+                .with_color(terminal.setting.pallete.white)
+                .with_scale(self.font_size)],
+            layout: wgpu_glyph::Layout::default_single_line(),
+        });
+    }
+
+    // draws the text an input method is composing, underlined over the cells from the cursor on.
+    // returns how many cells it covers
+    fn draw_preedit(&mut self, terminal: &Terminal) -> usize {
+        let (cell_width, cell_height) = terminal.layout.cell_size();
+        let pallete = &terminal.setting.pallete;
+        let x = self.gutter() + terminal.cursor.0 as f32 * cell_width;
+        let y = terminal.cursor.1 as f32 * cell_height;
+
+        let section = Section {
+            screen_position: (x, y),
+            bounds: (self.state.size.width as f32, self.state.size.height as f32),
+            text: vec![Text::new(&terminal.preedit)
+                .with_color(pallete.white)
+                .with_scale(self.font_size)],
+            layout: wgpu_glyph::Layout::default_single_line(),
+        };
+
+        let width = self
+            .brush
+            .glyph_bounds(&section)
+            .map_or(0.0, |bounds| bounds.width());
+        let columns = (width / cell_width).ceil().max(1.0);
+        let thickness = (cell_height / 10.0).max(1.0).round();
+
+        self.rects.queue(Rect::new(
+            x,
+            y,
+            columns * cell_width,
+            cell_height,
+            pallete.black,
+        ));
+        self.rects.queue(Rect::new(
+            x,
+            y + cell_height - thickness,
+            columns * cell_width,
+            thickness,
+            pallete.white,
+        ));
+        self.brush.queue(section);
+
+        columns as usize
+    }
+
+    // `cursor_shown` is false while a blinking cursor is hidden
+    pub fn draw_text(&mut self, terminal: &Terminal, focused: bool, cursor_shown: bool) {
+        let grid = &terminal.visible_grid;
+        let gutter = self.gutter();
+        let (_, cell_height) = terminal.layout.cell_size();
+        let covered_rows = std::mem::take(&mut self.covered_rows);
+
+        // the composed text takes the cursor's place until the input method commits it
+        let preedit_columns = if terminal.preedit.is_empty() || grid.display_offset != 0 {
+            0
+        } else {
+            self.draw_preedit(terminal)
+        };
+        let cursor_visible = terminal.visible_cursor
+            && grid.display_offset == 0
+            && cursor_shown
+            && preedit_columns == 0;
 
         for i in 0..(grid.size.1 as usize) {
+            if covered_rows.contains(&i) {
+                continue;
+            }
+            let row = grid.display_row(i);
+            let columns = row.attribute.columns(row.length);
+            let cursor_col = terminal.cursor.0.min(columns.saturating_sub(1)) as usize;
+
+            // double-height rows draw twice as tall text, cut down to the half they show
+            let (scale, y_offset) = match row.attribute {
+                LineAttribute::SingleWidth => (PxScale::from(self.font_size), None),
+                LineAttribute::DoubleWidth => (
+                    PxScale {
+                        x: self.font_size * 2.0,
+                        y: self.font_size,
+                    },
+                    None,
+                ),
+                LineAttribute::DoubleHeightTop => (PxScale::from(self.font_size * 2.0), Some(0.0)),
+                LineAttribute::DoubleHeightBottom => {
+                    (PxScale::from(self.font_size * 2.0), Some(cell_height))
+                }
+            };
+
+            let preedit = if i as u32 == terminal.cursor.1 {
+                cursor_col..cursor_col + preedit_columns
+            } else {
+                0..0
+            };
+
             let mut texts: Vec<Text> = vec![];
-            for j in 0..grid[i].length as usize {
-                if terminal.cursor == (j as u32, i as u32) && terminal.visible_cursor {
-                    texts.push(
-                        Text::new("█")
-                            .with_color([1.0, 1.0, 1.0, 1.0])
-                            .with_scale(self.font_size),
-                    );
-                    println!("cursor: {:?}", terminal.cursor);
-                    continue;
+            for j in 0..columns as usize {
+                // a space keeps the following cells in place
+                let mut text = if preedit.contains(&j) {
+                    Text::new(" ").with_scale(scale)
+                } else {
+                    row[j].text(self.font_size).with_scale(scale)
+                };
+                if cursor_visible && (cursor_col, terminal.cursor.1) == (j, i as u32) {
+                    text = text.with_color(self.draw_cursor(terminal, &row[j], focused));
                 }
-                texts.push(grid[i][j].text(self.font_size));
+                texts.push(text);
             }
 
-            // TODO: calculate different heights, etc.
+            // marks commands that exited with a non-zero status
+            if row.metadata.exit_status.is_some() {
+                self.brush.queue(Section {
+                    screen_position: (0.0, i as f32 * cell_height),
+                    bounds: (gutter, self.state.size.height as f32),
+                    text: vec![Text::new("▎")
+                        .with_color([1.0, 0.0, 0.0, 1.0])
+                        .with_scale(self.font_size)],
+                    layout: wgpu_glyph::Layout::default_single_line(),
+                });
+            }
 
-            self.brush.queue(Section {
-                screen_position: (0.0, (i * 20) as f32),
+            let y = i as f32 * cell_height;
+            let section = Section {
+                screen_position: (gutter, y - y_offset.unwrap_or(0.0)),
                 bounds: (self.state.size.width as f32, self.state.size.height as f32),
                 text: texts,
                 layout: wgpu_glyph::Layout::default_single_line(),
-            })
+            };
+
+            match y_offset {
+                Some(_) if (y as u32) < self.state.size.height => {
+                    let region = Region {
+                        x: 0,
+                        y: y as u32,
+                        width: self.state.size.width,
+                        height: (cell_height as u32).min(self.state.size.height - y as u32),
+                    };
+                    self.clipped.push((region, section.to_owned()));
+                }
+                Some(_) => {}
+                None => self.brush.queue(section),
+            }
         }
     }
 }
@@ -0,0 +1,163 @@
+// draws solid colored rectangles, used for things that aren't glyphs like the progress bar
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+// two floats of position followed by four floats of color
+const VERTEX_SIZE: u64 = 6 * 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: [f32; 4],
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            color,
+        }
+    }
+}
+
+pub struct RectRenderer {
+    pipeline: wgpu::RenderPipeline,
+    queued: Vec<Rect>,
+}
+
+impl RectRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Rect Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Rect Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Rect Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            queued: vec![],
+        }
+    }
+
+    pub fn queue(&mut self, rect: Rect) {
+        self.queued.push(rect);
+    }
+
+    // draws and clears every queued rect, positions are in pixels of a `width` by `height` surface
+    pub fn draw_queued<'a>(
+        &'a mut self,
+        device: &wgpu::Device,
+        pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a mut Option<wgpu::Buffer>,
+        width: u32,
+        height: u32,
+    ) {
+        if self.queued.is_empty() {
+            return;
+        }
+
+        let vertices = vertices(&self.queued, width as f32, height as f32);
+        let count = (vertices.len() / 6) as u32;
+        self.queued.clear();
+
+        let bytes = vertices
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect::<Vec<u8>>();
+
+        *buffer = Some(
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Rect Vertex Buffer"),
+                contents: &bytes,
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+        );
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, buffer.as_ref().unwrap().slice(..));
+        pass.draw(0..count, 0..1);
+    }
+}
+
+// two triangles per rect, converted from pixels to normalized device coordinates
+fn vertices(rects: &[Rect], width: f32, height: f32) -> Vec<f32> {
+    let mut vertices = Vec::with_capacity(rects.len() * 6 * 6);
+
+    for rect in rects {
+        let left = rect.x / width * 2.0 - 1.0;
+        let right = (rect.x + rect.width) / width * 2.0 - 1.0;
+        let top = 1.0 - rect.y / height * 2.0;
+        let bottom = 1.0 - (rect.y + rect.height) / height * 2.0;
+
+        for (x, y) in [
+            (left, top),
+            (left, bottom),
+            (right, bottom),
+            (left, top),
+            (right, bottom),
+            (right, top),
+        ] {
+            vertices.extend_from_slice(&[x, y]);
+            vertices.extend_from_slice(&rect.color);
+        }
+    }
+
+    vertices
+}
@@ -1,15 +1,56 @@
 // Pieces the renderer and the terminal together
 
-use crate::{display::display_ansi_text, render::Renderer, terminal::Terminal};
+use std::time::{Duration, Instant};
+
+use copypasta::{ClipboardContext, ClipboardProvider};
+
+use crate::{
+    config::{Action, Config},
+    display::display_ansi_text,
+    input::{
+        keyboard,
+        mouse::{self, MouseButton, MouseEvent},
+        unicode::UnicodeInput,
+    },
+    notification::{self, FreedesktopSink, NotificationSink},
+    render::Renderer,
+    terminal::Terminal,
+};
+
+// how long the window has to stay the same size before the program is told about it
+const RESIZE_DELAY: Duration = Duration::from_millis(50);
 
 pub struct Screen {
     renderer: Renderer,
     pub terminal: Terminal,
     background_color: wgpu::Color,
+    config: Config,
+    window_title: String,
+    notifier: Box<dyn NotificationSink>,
+    focused: bool,
+    // when the cursor last started a blink, reset on key presses so it shows while typing
+    blink_start: Instant,
+    cursor_shown: bool,
+    // output has changed the grid since the last render
+    render_pending: bool,
+    // the cell the mouse is over and the button held down on it
+    mouse_cell: (u32, u32),
+    mouse_button: Option<MouseButton>,
+    // smooth scrolling that hasn't added up to a whole line yet, in pixels
+    scroll_pixels: f64,
+    // the last key pressed ran a keybinding or was already encoded, so its text isn't sent
+    key_handled: bool,
+    // the cell the input method's candidate window was last placed under
+    ime_cursor: Option<(u32, u32)>,
+    // the character picker, while it's open keys go to it instead of the program
+    unicode_input: Option<UnicodeInput>,
+    // the program exited and the window isn't held open
+    close_requested: bool,
 }
 impl Screen {
     pub async fn new(window: winit::window::Window, config: crate::Config) -> Self {
         let size = window.inner_size();
+        window.set_ime_allowed(true);
 
         let layout = crate::layout::Layout::new(
             config.scale,
@@ -22,13 +63,56 @@ impl Screen {
 
         println!("height: {}, width: {}", text_height, text_width);
 
+        let mut terminal = Terminal::with_launch(layout, config.launch.clone());
+        terminal.cursor_style = config.cursor_style;
+        terminal.default_cursor_style = config.cursor_style;
+        terminal.clear_scrollback_on_reset = config.reset_clears_scrollback;
+
         Self {
-            renderer: Renderer::new(window, config.scale, config.font_size).await,
-            terminal: Terminal::new(layout),
+            renderer: Renderer::new(window, config.font_size).await,
+            terminal,
             background_color: config.background_color,
+            config,
+            window_title: String::new(),
+            notifier: Box::new(FreedesktopSink::default()),
+            focused: true,
+            blink_start: Instant::now(),
+            cursor_shown: true,
+            render_pending: false,
+            mouse_cell: (0, 0),
+            mouse_button: None,
+            scroll_pixels: 0.0,
+            key_handled: false,
+            ime_cursor: None,
+            unicode_input: None,
+            close_requested: false,
         }
     }
 
+    pub fn set_focused(&mut self, focused: bool) {
+        if focused != self.focused {
+            self.terminal.focus_changed(focused);
+        }
+        self.focused = focused;
+        self.blink_start = Instant::now();
+        self.render_pending = true;
+    }
+
+    // whether a blinking cursor is in the shown half of its blink, unfocused windows don't blink
+    fn cursor_blink_shown(&self) -> bool {
+        let interval = self.config.cursor_blink_interval.as_millis();
+        if !self.terminal.cursor_style.blinking || !self.focused || interval == 0 {
+            return true;
+        }
+
+        (self.blink_start.elapsed().as_millis() / interval).is_multiple_of(2)
+    }
+
+    // whether the program exited and the window should close with it
+    pub fn should_close(&self) -> bool {
+        self.close_requested
+    }
+
     pub fn window(&self) -> &winit::window::Window {
         self.renderer.window()
     }
@@ -42,19 +126,364 @@ impl Screen {
         self.terminal.resize(size);
     }
 
-    pub fn render(&mut self) {
-        self.renderer.draw_text(&self.terminal);
+    // draws on the next check of the terminal, unless synchronized output is holding drawing off
+    pub fn request_render(&mut self) {
+        self.render_pending = true;
+    }
+
+    fn render(&mut self) {
+        self.render_pending = false;
+        self.renderer.draw_images(&self.terminal);
+        self.cursor_shown = self.cursor_blink_shown();
+        if let Some(input) = &self.unicode_input {
+            self.renderer.draw_unicode_input(&self.terminal, input);
+        } else if let Some(exit) = self.terminal.exit_status {
+            let banner = format!("{}, press Enter to restart", exit);
+            self.renderer.draw_banner(&self.terminal, &banner);
+        }
+        self.renderer
+            .draw_text(&self.terminal, self.focused, self.cursor_shown);
+        self.renderer.draw_progress(self.terminal.progress);
         self.renderer.render();
+        self.place_ime();
+    }
+
+    // keeps the input method's candidate window just below the cursor
+    fn place_ime(&mut self) {
+        let cursor = self.terminal.cursor;
+        if self.ime_cursor == Some(cursor) {
+            return;
+        }
+
+        self.ime_cursor = Some(cursor);
+        let (x, y) = self.terminal.layout.cell_position(cursor.0, cursor.1 + 1);
+        self.window()
+            .set_ime_position(winit::dpi::PhysicalPosition::new(x, y));
+    }
+
+    pub fn ime(&mut self, event: &winit::event::Ime) {
+        match event {
+            winit::event::Ime::Preedit(text, _) => self.terminal.preedit = text.clone(),
+            winit::event::Ime::Commit(text) => match &mut self.unicode_input {
+                Some(input) => text.chars().for_each(|c| input.push(c)),
+                None => self.terminal.commit_text(text),
+            },
+            winit::event::Ime::Enabled | winit::event::Ime::Disabled => {
+                self.terminal.preedit.clear()
+            }
+        }
+
+        self.blink_start = Instant::now();
+        self.render_pending = true;
     }
 
     pub fn key_pressed(&mut self, key: &winit::event::KeyboardInput) {
         println!("key pressed: {:?}", key.virtual_keycode);
-        self.terminal.key_pressed(key);
+
+        if key.state == winit::event::ElementState::Pressed {
+            self.key_handled = false;
+            if let Some(keycode) = key.virtual_keycode {
+                let action = self
+                    .config
+                    .keybindings
+                    .iter()
+                    .find(|binding| {
+                        binding.matches(keycode, self.terminal.modifiers, &self.terminal.user_vars)
+                    })
+                    .map(|binding| binding.action);
+
+                if let Some(action) = action {
+                    self.perform(action);
+                    self.key_handled = true;
+                    return;
+                }
+
+                if self.unicode_input.is_some() {
+                    self.unicode_input_key(keycode);
+                    return;
+                }
+
+                // a held window waits for Enter to start the program again
+                if self.terminal.exit_status.is_some() {
+                    if keycode == winit::event::VirtualKeyCode::Return {
+                        self.terminal.restart();
+                        self.render_pending = true;
+                    }
+                    self.key_handled = true;
+                    return;
+                }
+            }
+        }
+
+        self.blink_start = Instant::now();
+        if self.terminal.key_pressed(key) {
+            self.key_handled = true;
+        }
+    }
+
+    // picks from the unicode input overlay, typed characters go to its query
+    fn unicode_input_key(&mut self, keycode: winit::event::VirtualKeyCode) {
+        use winit::event::VirtualKeyCode::*;
+
+        let input = match &mut self.unicode_input {
+            Some(input) => input,
+            None => return,
+        };
+
+        match keycode {
+            Escape => self.unicode_input = None,
+            Return | NumpadEnter => {
+                if let Some(text) = input.chosen().map(str::to_string) {
+                    self.terminal.commit_text(&text);
+                }
+                self.unicode_input = None;
+            }
+            Up => input.select_previous(),
+            Down | Tab => input.select_next(),
+            Back => input.pop(),
+            _ => return,
+        }
+
+        self.render_pending = true;
+    }
+
+    pub fn received_character(&mut self, c: char) {
+        // the key was already used by a keybinding or sent by the encoder
+        if self.key_handled {
+            return;
+        }
+
+        if let Some(input) = &mut self.unicode_input {
+            if !c.is_control() {
+                input.push(c);
+                self.render_pending = true;
+            }
+            return;
+        }
+
+        self.blink_start = Instant::now();
+        self.terminal.received_character(c);
+    }
+
+    // sends a mouse event to the program if it asked for it, holding shift keeps the mouse for
+    // the terminal itself
+    fn report_mouse(&mut self, event: MouseEvent) -> bool {
+        let modes = &self.terminal.modes;
+        if self.terminal.modifiers.shift() {
+            return false;
+        }
+
+        match mouse::encode(
+            modes.mouse,
+            modes.mouse_encoding,
+            event,
+            self.terminal.modifiers,
+            self.mouse_cell,
+        ) {
+            Some(report) => {
+                self.terminal.reply(report);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn mouse_moved(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        let cell = self.terminal.layout.cell_at(
+            position.x,
+            position.y,
+            self.terminal.width,
+            self.terminal.height,
+        );
+
+        if cell != self.mouse_cell {
+            self.mouse_cell = cell;
+            self.report_mouse(MouseEvent::Motion(self.mouse_button));
+        }
+    }
+
+    pub fn mouse_input(
+        &mut self,
+        state: winit::event::ElementState,
+        button: winit::event::MouseButton,
+    ) {
+        let button = match button {
+            winit::event::MouseButton::Left => MouseButton::Left,
+            winit::event::MouseButton::Middle => MouseButton::Middle,
+            winit::event::MouseButton::Right => MouseButton::Right,
+            winit::event::MouseButton::Other(_) => return,
+        };
+
+        let event = match state {
+            winit::event::ElementState::Pressed => {
+                self.mouse_button = Some(button);
+                MouseEvent::Press(button)
+            }
+            winit::event::ElementState::Released => {
+                self.mouse_button = None;
+                MouseEvent::Release(button)
+            }
+        };
+
+        self.report_mouse(event);
+    }
+
+    // reports the wheel to the program, turns it into arrow keys on the alternate screen or
+    // otherwise scrolls through the scrollback
+    pub fn mouse_wheel(&mut self, delta: winit::event::MouseScrollDelta) {
+        // positive deltas move the content right and down, which is scrolling left and up
+        let (columns, lines) = match delta {
+            winit::event::MouseScrollDelta::LineDelta(x, y) => (x.round() as f64, y.round() as f64),
+            winit::event::MouseScrollDelta::PixelDelta(position) => {
+                let (_, cell_height) = self.terminal.layout.cell_size();
+                self.scroll_pixels += position.y;
+                let lines = (self.scroll_pixels / cell_height as f64).trunc();
+                self.scroll_pixels -= lines * cell_height as f64;
+                (0.0, lines)
+            }
+        };
+
+        // sideways scrolling only goes to programs
+        let button = if columns > 0.0 {
+            MouseButton::WheelLeft
+        } else {
+            MouseButton::WheelRight
+        };
+        for _ in 0..columns.abs() as usize {
+            self.report_mouse(MouseEvent::Press(button));
+        }
+
+        let (count, up) = (lines.abs() as usize, lines > 0.0);
+        if count == 0 {
+            return;
+        }
+
+        let button = if up {
+            MouseButton::WheelUp
+        } else {
+            MouseButton::WheelDown
+        };
+        if self.report_mouse(MouseEvent::Press(button)) {
+            for _ in 1..count {
+                self.report_mouse(MouseEvent::Press(button));
+            }
+            return;
+        }
+
+        let modes = &self.terminal.modes;
+        if modes.alternate_screen && modes.alternate_scroll {
+            // the arrow keys as they'd be typed, `SS3 A` in application cursor mode
+            let key = if up {
+                winit::event::VirtualKeyCode::Up
+            } else {
+                winit::event::VirtualKeyCode::Down
+            };
+            if let Some(arrow) = keyboard::encode(key, winit::event::ModifiersState::empty(), modes)
+            {
+                self.terminal.reply(arrow.repeat(count));
+            }
+        } else if !modes.alternate_screen {
+            let lines = if up {
+                count as isize
+            } else {
+                -(count as isize)
+            };
+            self.terminal.visible_grid.scroll_display(lines);
+            self.render_pending = true;
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::SpawnNewWindow => {
+                let mut command = match std::env::current_exe() {
+                    Ok(exe) => std::process::Command::new(exe),
+                    Err(e) => {
+                        println!("unable to find nyat executable: {}", e);
+                        return;
+                    }
+                };
+
+                // `-d` rather than only the directory of the process, so its PWD is right too
+                let dir = self.terminal.working_directory();
+                command.args(self.config.new_window_args(dir.as_deref()));
+
+                if let Err(e) = command.spawn() {
+                    println!("unable to spawn new window: {}", e);
+                }
+            }
+            Action::PreviousPrompt => {
+                self.terminal.scroll_to_prompt(true);
+                self.render_pending = true;
+            }
+            Action::NextPrompt => {
+                self.terminal.scroll_to_prompt(false);
+                self.render_pending = true;
+            }
+            Action::CopyLastCommandOutput => {
+                if let Some(output) = self.terminal.last_command_output() {
+                    let result = ClipboardContext::new()
+                        .and_then(|mut clipboard| clipboard.set_contents(output));
+
+                    if let Err(e) = result {
+                        println!("unable to copy to clipboard: {}", e);
+                    }
+                }
+            }
+            Action::Paste => {
+                match ClipboardContext::new().and_then(|mut clipboard| clipboard.get_contents()) {
+                    Ok(text) => self.terminal.paste(&text),
+                    Err(e) => println!("unable to paste from clipboard: {}", e),
+                }
+            }
+            Action::UnicodeInput => {
+                self.unicode_input = Some(UnicodeInput::default());
+                self.render_pending = true;
+            }
+        }
+    }
+
+    fn update_title(&mut self) {
+        let title = self
+            .terminal
+            .window_title(&self.config.title_format, self.config.progress_in_title);
+
+        if title != self.window_title {
+            self.window().set_title(&title);
+            self.window_title = title;
+        }
     }
 
     pub fn check_term(&mut self) {
+        self.terminal.flush_pending_key();
+        self.terminal.report_size(RESIZE_DELAY);
+
+        if self.terminal.child_exited().is_some() {
+            self.close_requested = !self.config.hold;
+            self.render_pending = true;
+        }
+
         if let Ok(s) = self.terminal.reciever.try_recv() {
             display_ansi_text(&mut self.terminal, s);
+            notification::deliver(
+                std::mem::take(&mut self.terminal.notifications),
+                self.notifier.as_mut(),
+                self.focused,
+                self.config.notify_only_when_unfocused,
+            );
+            self.update_title();
+            self.render_pending = true;
+        }
+
+        // programs using synchronized output get their whole frame drawn at once
+        if self
+            .terminal
+            .rendering_suspended(self.config.synchronized_output_timeout)
+        {
+            return;
+        }
+
+        if self.render_pending || self.cursor_blink_shown() != self.cursor_shown {
             self.render();
         }
     }
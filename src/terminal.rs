@@ -1,160 +1,651 @@
 use std::{
-    ffi::{CStr, CString},
-    os::fd::AsRawFd,
-    sync::mpsc::Sender,
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::PathBuf,
+    sync::mpsc::{Receiver, Sender, TryRecvError},
+    time::{Duration, Instant},
 };
 
-use winit::event::{ModifiersState, VirtualKeyCode};
+use winit::event::ModifiersState;
 
 use nix::{
-    libc::{ioctl, TIOCSCTTY},
+    libc::{ioctl, winsize, TIOCSCTTY, TIOCSWINSZ},
     poll::{PollFd, PollFlags},
     pty::{grantpt, posix_openpt, ptsname, unlockpt},
-    sys::{
-        select::{select, FdSet},
-        termios::{cfmakeraw, tcsetattr, SetArg},
-    },
-    unistd::{close, dup, execvp, fork, read, setsid},
+    sys::termios::{cfmakeraw, tcsetattr, SetArg},
+    sys::wait::{waitpid, WaitStatus},
+    unistd::{close, dup, fork, read, setsid, tcgetpgrp, ForkResult},
 };
 
 // holds grid and later on will hold the cursor position
 // Also will hold psuedo terminal
-use crate::layout::{Grid, Layout};
+use crate::{
+    display::Setting,
+    graphics::{kitty, ImageStore},
+    input::{
+        keyboard::{self, KeyEvent, KeyEventKind},
+        mouse::{MouseEncoding, MouseMode},
+        paste,
+    },
+    layout::{Grid, Layout, PromptMark},
+    notification::Notification,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressState {
+    Normal,
+    Error,
+    Indeterminate,
+    Paused,
+}
+
+// progress reported with the ConEmu `OSC 9;4;state;percent` sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub state: ProgressState,
+    pub percent: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+
+// the cursor style chosen with DECSCUSR, `CSI Ps SP q`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl CursorStyle {
+    pub fn new(shape: CursorShape, blinking: bool) -> Self {
+        Self { shape, blinking }
+    }
+
+    // the style a DECSCUSR parameter selects, 0 selects `default`
+    pub fn from_decscusr(ps: u16, default: CursorStyle) -> Option<Self> {
+        let shape = match ps {
+            0 => return Some(default),
+            1 | 2 => CursorShape::Block,
+            3 | 4 => CursorShape::Underline,
+            5 | 6 => CursorShape::Bar,
+            _ => return None,
+        };
+
+        Some(Self::new(shape, ps % 2 == 1))
+    }
+
+    // the DECSCUSR parameter that selects this style
+    pub fn decscusr(&self) -> u16 {
+        let steady = match self.shape {
+            CursorShape::Block => 2,
+            CursorShape::Underline => 4,
+            CursorShape::Bar => 6,
+        };
+
+        if self.blinking {
+            steady - 1
+        } else {
+            steady
+        }
+    }
+}
+
+// modes programs switch with SM/RM and DECSET/DECRST
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modes {
+    // DECCKM, cursor keys send `SS3` sequences instead of `CSI` ones
+    pub application_cursor: bool,
+    // DECKPAM and DECKPNM, `ESC =` and `ESC >`, the keypad sends `SS3` sequences instead of text
+    pub application_keypad: bool,
+    // xterm's modifyOtherKeys level, `CSI > 4 ; Pv m`, above 0 modified keys are sent as
+    // `CSI 27 ; mod ; code ~`
+    pub modify_other_keys: u8,
+    // DECAWM, wrap onto the next line when printing past the last column
+    pub autowrap: bool,
+    // 2004, wrap pasted text in `CSI 200 ~` and `CSI 201 ~`
+    pub bracketed_paste: bool,
+    pub mouse: MouseMode,
+    pub mouse_encoding: MouseEncoding,
+    // 1007, the mouse wheel sends arrow keys on the alternate screen when it isn't reported
+    pub alternate_scroll: bool,
+    // 47, 1047 and 1049
+    pub alternate_screen: bool,
+    // 1004, send `CSI I` and `CSI O` when the window gains and loses focus
+    pub focus_reporting: bool,
+    // 2026, hold off drawing while the program updates the screen
+    pub synchronized_output: bool,
+}
+
+impl Default for Modes {
+    fn default() -> Self {
+        Self {
+            application_cursor: false,
+            application_keypad: false,
+            modify_other_keys: 0,
+            autowrap: true,
+            bracketed_paste: false,
+            mouse: MouseMode::Off,
+            mouse_encoding: MouseEncoding::Default,
+            alternate_scroll: true,
+            alternate_screen: false,
+            focus_reporting: false,
+            synchronized_output: false,
+        }
+    }
+}
+
+// how the command running in the pty ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChildExit {
+    Code(i32),
+    Signal(i32),
+    // the thread reading the pty stopped without seeing the command end
+    Lost,
+}
+
+impl std::fmt::Display for ChildExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChildExit::Code(code) => write!(f, "process exited with code {}", code),
+            ChildExit::Signal(signal) => write!(f, "process was killed by signal {}", signal),
+            ChildExit::Lost => write!(f, "lost the connection to the process"),
+        }
+    }
+}
+
+// what gets run in the pty, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    // set on top of the inherited environment and the defaults
+    pub env: Vec<(String, String)>,
+    // inherited or default variables left out
+    pub env_remove: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    // runs it as a login shell, with a `-` in front of argv[0]
+    pub login: bool,
+}
+
+impl LaunchSpec {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: vec![],
+            env: vec![],
+            env_remove: vec![],
+            cwd: None,
+            login: false,
+        }
+    }
+
+    // the user's shell
+    pub fn shell() -> Self {
+        Self::new(std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string()))
+    }
+
+    fn argv(&self) -> Vec<CString> {
+        let program = if self.login {
+            let name = self.program.rsplit('/').next().unwrap_or(&self.program);
+            format!("-{}", name)
+        } else {
+            self.program.clone()
+        };
+
+        std::iter::once(program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .filter_map(|arg| CString::new(arg).ok())
+            .collect()
+    }
+
+    // the inherited environment with the defaults and the spec's changes applied
+    fn env(&self) -> Vec<(String, String)> {
+        // variables that aren't unicode are dropped rather than panicking like `env::vars`
+        let mut env = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .filter(|(key, _)| !matches!(key.as_str(), "TERM" | "COLORTERM" | "TERM_PROGRAM"))
+            .collect::<Vec<_>>();
+
+        env.push(("TERM".to_string(), "xterm-256color".to_string()));
+        env.push(("COLORTERM".to_string(), "truecolor".to_string()));
+        env.push(("TERM_PROGRAM".to_string(), "nyat".to_string()));
+
+        // the inherited PWD would name the directory nyat was started in
+        if let Some(cwd) = self.cwd.as_ref().and_then(|cwd| cwd.to_str()) {
+            env.retain(|(key, _)| key != "PWD");
+            env.push(("PWD".to_string(), cwd.to_string()));
+        }
+
+        for (key, value) in &self.env {
+            env.retain(|(k, _)| k != key);
+            env.push((key.clone(), value.clone()));
+        }
+        env.retain(|(key, _)| !self.env_remove.contains(key));
+
+        env
+    }
+
+    fn envp(&self) -> Vec<CString> {
+        self.env()
+            .into_iter()
+            .filter_map(|(key, value)| CString::new(format!("{}={}", key, value)).ok())
+            .collect()
+    }
+}
+
+// how long the working directory read from /proc is reused in the title before it's looked up
+// again, shells that report it through OSC 7 never need the lookup
+const CWD_LOOKUP_INTERVAL: Duration = Duration::from_secs(1);
+
+// how deep the kitty keyboard protocol stack of each screen gets
+const MAX_KEYBOARD_MODES: usize = 16;
+
+// sets the size of the pty in cells and pixels, which sends SIGWINCH to its foreground programs
+fn set_window_size(fd: RawFd, layout: &Layout, width: u32, height: u32) {
+    let (cell_width, cell_height) = layout.cell_size();
+    let size = winsize {
+        ws_row: height as u16,
+        ws_col: width as u16,
+        ws_xpixel: (width as f32 * cell_width) as u16,
+        ws_ypixel: (height as f32 * cell_height) as u16,
+    };
+
+    if unsafe { ioctl(fd, TIOCSWINSZ, &size) } < 0 {
+        println!(
+            "unable to set the pty size: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+// a tab stop every 8 columns
+// turns what was read from the pty into text. a character split across reads waits in
+// `incomplete` for the rest of its bytes, anything else that isn't utf-8 is replaced
+fn decode_output(incomplete: &mut Vec<u8>, input: &[u8]) -> String {
+    incomplete.extend_from_slice(input);
+
+    let start = incomplete.len().saturating_sub(3);
+    let end = (start..incomplete.len())
+        .find(|&i| {
+            matches!(std::str::from_utf8(&incomplete[i..]),
+                Err(e) if e.valid_up_to() == 0 && e.error_len().is_none())
+        })
+        .unwrap_or(incomplete.len());
+
+    let text = String::from_utf8_lossy(&incomplete[..end]).into_owned();
+    incomplete.drain(..end);
+    text
+}
+
+fn default_tab_stops(width: u32) -> Vec<bool> {
+    (0..width).map(|col| col % 8 == 0).collect()
+}
 
 #[derive(Debug)]
 pub struct Terminal {
+    // the screen being shown, the normal or the alternate one
     pub visible_grid: Grid,
+    // the screen that isn't
+    other_grid: Grid,
+    // where the cursor was on the normal screen when DECSET 1049 switched away from it
+    saved_cursor: (u32, u32),
+    // when synchronized output was last turned on
+    synchronized_since: Instant,
     pub width: u32,
     pub height: u32,
     pub cursor: (u32, u32),
     pub visible_cursor: bool,
+    pub cursor_style: CursorStyle,
+    // what `CSI 0 SP q` goes back to
+    pub default_cursor_style: CursorStyle,
     pub layout: Layout,
+    pub modes: Modes,
+    pub tab_stops: Vec<bool>,
+    // whether RIS also drops the scrollback
+    pub clear_scrollback_on_reset: bool,
     pub reciever: std::sync::mpsc::Receiver<String>,
     pub transmitter: std::sync::mpsc::Sender<String>,
     pub modifiers: winit::event::ModifiersState,
+    pub title: String,
+    // last directory reported by the shell through OSC 7
+    pub current_dir: Option<PathBuf>,
+    // the fallback working directory last shown in the title, and when it was looked up
+    looked_up_dir: Option<(Instant, Option<PathBuf>)>,
+    // notifications waiting to be delivered by the screen
+    pub notifications: Vec<Notification>,
+    // an OSC 99 notification that is still receiving chunks
+    pub kitty_notification: Option<Notification>,
+    pub progress: Option<Progress>,
+    // variables published by the shell with OSC 1337 SetUserVar
+    pub user_vars: HashMap<String, String>,
+    pub setting: Setting,
+    pub images: ImageStore,
+    pub kitty_graphics: kitty::State,
+    // the kitty keyboard protocol flags pushed by programs, the last ones are in effect
+    pub keyboard_modes: Vec<u32>,
+    // the stack of the screen that isn't shown, each screen has its own
+    other_keyboard_modes: Vec<u32>,
+    // scancodes of the keys held down, to tell repeats from presses
+    pressed_keys: HashSet<u32>,
+    // a key that produces text, waiting for its character before being encoded
+    pending_key: Option<KeyEvent>,
+    // text an input method is composing, shown at the cursor until it's committed
+    pub preedit: String,
+    // the start of a string sequence, like a sixel image, that hasn't been fully read yet
+    pub pending: String,
+    master_fd: RawFd,
+    // the command the pty runs, kept to start it again
+    launch: LaunchSpec,
+    exit_reciever: Receiver<ChildExit>,
+    // how the command ended, once it has
+    pub exit_status: Option<ChildExit>,
+    // when the window was last resized, if the pty hasn't been told yet
+    resized_at: Option<Instant>,
 }
 
 impl Terminal {
+    #[cfg(test)]
     pub fn new(layout: Layout) -> Self {
+        Self::with_launch(layout, LaunchSpec::shell())
+    }
+
+    // runs `launch` instead of the user's shell
+    pub fn with_launch(layout: Layout, launch: LaunchSpec) -> Self {
         let (width, height) = layout.calculate();
 
         let visible_grid = Grid::new(width, height);
-
-        let default_shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let mut other_grid = Grid::new(width, height);
+        other_grid.history = false;
 
         let (tx, reciever) = std::sync::mpsc::channel();
+        let (exit_tx, exit_reciever) = std::sync::mpsc::channel();
 
-        let transmitter = Self::spawn_pty_with_shell(default_shell, &layout, width, height, tx);
+        let (transmitter, master_fd) =
+            Self::spawn_pty_with_shell(&launch, &layout, width, height, tx, exit_tx);
 
-        let term = Self {
+        Self {
             visible_grid,
+            other_grid,
+            saved_cursor: (0, 0),
+            synchronized_since: Instant::now(),
             width,
             height,
             layout,
+            modes: Modes::default(),
+            tab_stops: default_tab_stops(width),
+            clear_scrollback_on_reset: false,
             cursor: (0, 0),
             visible_cursor: true,
+            cursor_style: CursorStyle::new(CursorShape::Block, true),
+            default_cursor_style: CursorStyle::new(CursorShape::Block, true),
             reciever,
             transmitter,
             modifiers: ModifiersState::empty(),
-        };
-
-        term
+            title: String::new(),
+            current_dir: None,
+            looked_up_dir: None,
+            notifications: vec![],
+            kitty_notification: None,
+            progress: None,
+            user_vars: HashMap::new(),
+            setting: Setting::default(),
+            images: ImageStore::default(),
+            kitty_graphics: kitty::State::default(),
+            keyboard_modes: vec![],
+            other_keyboard_modes: vec![],
+            pressed_keys: HashSet::new(),
+            pending_key: None,
+            preedit: String::new(),
+            pending: String::new(),
+            master_fd,
+            launch,
+            exit_reciever,
+            exit_status: None,
+            resized_at: None,
+        }
     }
 
     fn spawn_pty_with_shell(
-        shell: String,
-        _layout: &Layout,
-        _width: u32,
-        _height: u32,
+        launch: &LaunchSpec,
+        layout: &Layout,
+        width: u32,
+        height: u32,
         transmitter: Sender<String>,
-    ) -> Sender<String> {
-        let fdm = posix_openpt(nix::fcntl::OFlag::O_RDWR).unwrap();
+        exit_transmitter: Sender<ChildExit>,
+    ) -> (Sender<String>, RawFd) {
+        // close-on-exec, so other ptys' children don't keep this one open after its child exits
+        let fdm = posix_openpt(nix::fcntl::OFlag::O_RDWR | nix::fcntl::OFlag::O_CLOEXEC).unwrap();
+        let master_fd = fdm.as_raw_fd();
 
         grantpt(&fdm).unwrap();
         unlockpt(&fdm).unwrap();
 
+        // the shell reads its size on startup, so it has to be set before it runs
+        set_window_size(master_fd, layout, width, height);
+
         let pts_name = unsafe { ptsname(&fdm) }.unwrap();
 
         let fds = nix::fcntl::open(
             &std::path::PathBuf::from(pts_name),
-            nix::fcntl::OFlag::O_RDWR,
+            nix::fcntl::OFlag::O_RDWR | nix::fcntl::OFlag::O_CLOEXEC,
             nix::sys::stat::Mode::empty(),
         )
         .unwrap();
 
         let (tx, reciever) = std::sync::mpsc::channel::<String>();
 
-        std::thread::spawn(move || match unsafe { fork() } {
-            Ok(res) => {
-                if res.is_parent() {
-                    nix::unistd::close(fds).unwrap();
+        // built before forking, allocating in the child of a multithreaded process can deadlock
+        // on a lock another thread held
+        let program = CString::new(launch.program.as_str()).unwrap_or_default();
+        let args = launch.argv();
+        let env = launch.envp();
+        let cwd = launch
+            .cwd
+            .as_ref()
+            .and_then(|cwd| CString::new(cwd.as_os_str().as_bytes()).ok());
+        let cwd_error = format!(
+            "nyat: unable to change directory to {}\r\n",
+            launch
+                .cwd
+                .as_ref()
+                .map_or(String::new(), |cwd| cwd.display().to_string())
+        );
+        let exec_error = format!("nyat: unable to run {}\r\n", launch.program);
 
-                    loop {
-                        if let Ok(x) = reciever.try_recv() {
-                            nix::unistd::write(fdm.as_raw_fd(), x.as_bytes()).unwrap();
-                        }
-                        let pollfd = PollFd::new(fdm.as_raw_fd(), PollFlags::POLLIN);
+        std::thread::spawn(move || {
+            let mut argv = args.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+            argv.push(std::ptr::null());
+            let mut envp = env.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+            envp.push(std::ptr::null());
 
-                        let rc = nix::poll::poll(&mut [pollfd], 10).unwrap();
-                        // let rc = 1;
+            match unsafe { fork() } {
+                Ok(res) => {
+                    if let ForkResult::Parent { child } = res {
+                        nix::unistd::close(fds).unwrap();
+                        let mut incomplete = vec![];
 
-                        if rc > 0 {
-                            let mut input = [0u8; 65536];
-                            let rc = read(fdm.as_raw_fd(), &mut input).unwrap();
-                            if rc > 0 {
-                                let s = String::from_utf8(input[..rc].to_vec()).unwrap();
+                        loop {
+                            if let Ok(x) = reciever.try_recv() {
+                                // the child may have just exited, which the read below notices
+                                let _ = nix::unistd::write(fdm.as_raw_fd(), x.as_bytes());
+                            }
+                            let pollfd = PollFd::new(fdm.as_raw_fd(), PollFlags::POLLIN);
 
-                                match transmitter.send(s) {
-                                    Ok(_) => {}
-                                    Err(e) => {
-                                        panic!("send failed: {}", e);
-                                    }
+                            let rc = match nix::poll::poll(&mut [pollfd], 10) {
+                                Ok(rc) => rc,
+                                Err(nix::errno::Errno::EINTR) => continue,
+                                Err(_) => break,
+                            };
+
+                            if rc > 0 {
+                                let mut input = [0u8; 65536];
+                                // reading fails with EIO once the child and everything it started
+                                // have closed the pty
+                                let rc = match read(fdm.as_raw_fd(), &mut input) {
+                                    Ok(rc) if rc > 0 => rc,
+                                    _ => break,
                                 };
+                                let s = decode_output(&mut incomplete, &input[..rc]);
+
+                                // the terminal is gone, nothing is left to show the output
+                                if transmitter.send(s).is_err() {
+                                    break;
+                                }
                             }
                         }
-                    }
-                } else {
-                    close(fdm.as_raw_fd()).unwrap();
 
-                    let slave_orig_settings = nix::sys::termios::tcgetattr(fds).unwrap();
-                    let mut new_term_settings = slave_orig_settings;
-                    cfmakeraw(&mut new_term_settings);
-                    tcsetattr(fds, SetArg::TCSANOW, &new_term_settings).unwrap();
+                        if !incomplete.is_empty() {
+                            let _ = transmitter.send(String::from_utf8_lossy(&incomplete).into());
+                        }
+
+                        let exit = match waitpid(child, None) {
+                            Ok(WaitStatus::Signaled(_, signal, _)) => {
+                                ChildExit::Signal(signal as i32)
+                            }
+                            Ok(WaitStatus::Exited(_, code)) => ChildExit::Code(code),
+                            _ => ChildExit::Code(-1),
+                        };
+                        let _ = exit_transmitter.send(exit);
 
-                    close(0).unwrap();
-                    close(1).unwrap();
-                    close(2).unwrap();
+                        // input is dropped until the terminal is restarted or closed, so writing
+                        // to it still works
+                        for _ in reciever {}
+                    } else {
+                        close(fdm.as_raw_fd()).unwrap();
 
-                    dup(fds).unwrap();
-                    dup(fds).unwrap();
-                    dup(fds).unwrap();
+                        let slave_orig_settings = nix::sys::termios::tcgetattr(fds).unwrap();
+                        let mut new_term_settings = slave_orig_settings;
+                        cfmakeraw(&mut new_term_settings);
+                        tcsetattr(fds, SetArg::TCSANOW, &new_term_settings).unwrap();
 
-                    close(fds).unwrap();
+                        close(0).unwrap();
+                        close(1).unwrap();
+                        close(2).unwrap();
 
-                    setsid().unwrap();
+                        dup(fds).unwrap();
+                        dup(fds).unwrap();
+                        dup(fds).unwrap();
 
-                    unsafe {
-                        ioctl(0, TIOCSCTTY, 1);
-                    };
+                        close(fds).unwrap();
 
-                    {
-                        let cmd_arr = shell
-                            .split_whitespace()
-                            .map(|s| CString::new(s).unwrap())
-                            .collect::<Vec<CString>>();
+                        setsid().unwrap();
 
-                        let cmd_arr = cmd_arr.iter().map(|s| s.as_c_str()).collect::<Vec<&CStr>>();
+                        unsafe {
+                            ioctl(0, TIOCSCTTY, 1);
+                        };
 
-                        let _rc = execvp(cmd_arr[0], &cmd_arr);
+                        // a missing directory is reported, but the command still runs
+                        if let Some(cwd) = &cwd {
+                            if unsafe { nix::libc::chdir(cwd.as_ptr()) } != 0 {
+                                let _ = nix::unistd::write(2, cwd_error.as_bytes());
+                            }
+                        }
+
+                        // like shells, 127 when the command can't be run
+                        unsafe {
+                            nix::libc::execvpe(program.as_ptr(), argv.as_ptr(), envp.as_ptr());
+                            let _ = nix::unistd::write(2, exec_error.as_bytes());
+                            nix::libc::_exit(127);
+                        }
                     }
                 }
-            }
-            Err(e) => {
-                panic!("fork failed: {}", e);
+                Err(e) => {
+                    panic!("fork failed: {}", e);
+                }
             }
         });
 
-        tx
+        (tx, master_fd)
+    }
+
+    // the directory reported by OSC 7, otherwise the cwd of the foreground process group
+    pub fn working_directory(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.current_dir {
+            return Some(dir.clone());
+        }
+
+        let pgrp = tcgetpgrp(self.master_fd).ok()?;
+        std::fs::read_link(format!("/proc/{}/cwd", pgrp)).ok()
+    }
+
+    // the working directory for the title, which is updated on every chunk of output so the /proc
+    // lookup is only redone every so often
+    fn title_directory(&mut self) -> Option<PathBuf> {
+        if let Some(dir) = &self.current_dir {
+            return Some(dir.clone());
+        }
+
+        match &self.looked_up_dir {
+            Some((at, dir)) if at.elapsed() < CWD_LOOKUP_INTERVAL => dir.clone(),
+            _ => {
+                let dir = self.working_directory();
+                self.looked_up_dir = Some((Instant::now(), dir.clone()));
+                dir
+            }
+        }
+    }
+
+    // expands `{title}`, `{cwd}` and `{user.NAME}` in the configured title format, optionally
+    // prefixed with the progress percentage
+    pub fn window_title(&mut self, format: &str, show_progress: bool) -> String {
+        let mut title = String::new();
+        let mut rest = format;
+
+        // each placeholder is expanded once, so text the program controls is never expanded again
+        while let Some(start) = rest.find('{') {
+            title.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let placeholder = match rest[1..].find(['{', '}']) {
+                Some(end) if rest.as_bytes()[end + 1] == b'}' => &rest[1..end + 1],
+                _ => {
+                    title.push('{');
+                    rest = &rest[1..];
+                    continue;
+                }
+            };
+            rest = &rest[placeholder.len() + 2..];
+
+            match placeholder {
+                "title" if self.title.is_empty() => title.push_str("Nyat"),
+                "title" => title.push_str(&self.title),
+                "cwd" => {
+                    if let Some(dir) = self.title_directory() {
+                        title.push_str(&dir.display().to_string());
+                    }
+                }
+                // variables that haven't been set expand to nothing
+                _ => match placeholder.strip_prefix("user.") {
+                    Some(name) => {
+                        if let Some(value) = self.user_vars.get(name) {
+                            title.push_str(value);
+                        }
+                    }
+                    None => {
+                        title.push('{');
+                        title.push_str(placeholder);
+                        title.push('}');
+                    }
+                },
+            }
+        }
+        title.push_str(rest);
+
+        match self.progress {
+            Some(progress) if show_progress => match progress.state {
+                ProgressState::Indeterminate => format!("[…] {}", title),
+                _ => format!("[{}%] {}", progress.percent, title),
+            },
+            _ => title,
+        }
     }
 
     pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
@@ -164,68 +655,586 @@ impl Terminal {
         (self.width, self.height) = self.layout.calculate();
         println!("height: {}, width: {}", self.height, self.width);
         self.visible_grid.resize(self.width, self.height);
+        self.other_grid.resize(self.width, self.height);
+
+        let defaults = default_tab_stops(self.width);
+        self.tab_stops = (0..self.width as usize)
+            .map(|col| self.tab_stops.get(col).copied().unwrap_or(defaults[col]))
+            .collect();
+
+        self.resized_at = Some(Instant::now());
+    }
+
+    // how the child exited, the first time it's noticed
+    pub fn child_exited(&mut self) -> Option<ChildExit> {
+        if self.exit_status.is_some() {
+            return None;
+        }
+
+        let exit = match self.exit_reciever.try_recv() {
+            Ok(exit) => exit,
+            Err(TryRecvError::Empty) => return None,
+            // the reader thread died, so the exit would never be seen
+            Err(TryRecvError::Disconnected) => ChildExit::Lost,
+        };
+        self.exit_status = Some(exit);
+        Some(exit)
+    }
+
+    // starts the command again in a fresh pty after it exited, on a reset screen
+    pub fn restart(&mut self) {
+        let (tx, reciever) = std::sync::mpsc::channel();
+        let (exit_tx, exit_reciever) = std::sync::mpsc::channel();
+
+        let (transmitter, master_fd) = Self::spawn_pty_with_shell(
+            &self.launch,
+            &self.layout,
+            self.width,
+            self.height,
+            tx,
+            exit_tx,
+        );
+
+        self.reciever = reciever;
+        self.transmitter = transmitter;
+        self.master_fd = master_fd;
+        self.exit_reciever = exit_reciever;
+        self.exit_status = None;
+        self.pending.clear();
+        self.full_reset();
+    }
+
+    // tells the pty the new size once the window has stopped resizing for `delay`, so a drag
+    // doesn't flood the program with SIGWINCH
+    pub fn report_size(&mut self, delay: Duration) {
+        match self.resized_at {
+            Some(at) if at.elapsed() >= delay => {
+                set_window_size(self.master_fd, &self.layout, self.width, self.height);
+                self.resized_at = None;
+            }
+            _ => {}
+        }
     }
 
-    pub fn key_pressed(&mut self, key: &winit::event::KeyboardInput) {
-        if key.state != winit::event::ElementState::Pressed {
+    // DECSTR, the VT220 soft reset: shows the cursor, forgets the saved cursor and resets the pen
+    // and character sets while leaving the screen alone. autowrap is left as it is like xterm
+    // does, `reset` and `tput init` send DECSTR and wrapping has to survive them
+    pub fn soft_reset(&mut self) {
+        self.visible_cursor = true;
+        self.modes.application_cursor = false;
+        self.modes.application_keypad = false;
+        self.saved_cursor = (0, 0);
+        self.setting = Setting::default();
+    }
+
+    // RIS, puts the terminal back the way it started
+    pub fn full_reset(&mut self) {
+        if self.modes.alternate_screen {
+            self.set_alternate_screen(false, false);
+        }
+
+        self.soft_reset();
+        self.modes = Modes::default();
+        self.tab_stops = default_tab_stops(self.width);
+        self.cursor = (0, 0);
+        self.cursor_style = self.default_cursor_style;
+        self.title.clear();
+        self.progress = None;
+        self.kitty_graphics = kitty::State::default();
+        self.kitty_notification = None;
+        self.user_vars.clear();
+        self.current_dir = None;
+        self.keyboard_modes.clear();
+        self.other_keyboard_modes.clear();
+
+        self.visible_grid.clear(self.clear_scrollback_on_reset);
+
+        // only images still shown in the scrollback are kept
+        let placed = self.placed_images();
+        let unplaced = self
+            .images
+            .ids()
+            .copied()
+            .filter(|id| !placed.contains(id))
+            .collect::<Vec<u32>>();
+        for id in unplaced {
+            self.images.remove(id);
+        }
+    }
+
+    // the ids of the images placed on either screen
+    fn placed_images(&self) -> HashSet<u32> {
+        self.visible_grid
+            .placements
+            .iter()
+            .chain(self.other_grid.placements.iter())
+            .map(|p| p.image_id)
+            .collect()
+    }
+
+    // drops anonymous images that are no longer placed on either screen
+    pub fn prune_images(&mut self) {
+        let placed = self.placed_images();
+        self.images.prune(&placed);
+    }
+
+    // switches between the normal and alternate screens, the alternate screen is cleared on
+    // the way in. `save_cursor` keeps the cursor of the normal screen like DECSET 1049
+    pub fn set_alternate_screen(&mut self, alternate: bool, save_cursor: bool) {
+        if alternate == self.modes.alternate_screen {
             return;
         }
-        self.transmitter.send(self.key_to_string(key)).unwrap();
-    }
-
-    fn key_to_string(&self, key: &winit::event::KeyboardInput) -> String {
-        let mut s = String::new();
-
-        if let Some(keycode) = key.virtual_keycode {
-            match keycode {
-                VirtualKeyCode::A => s.push('a'),
-                VirtualKeyCode::B => s.push('b'),
-                VirtualKeyCode::C => s.push('c'),
-                VirtualKeyCode::D => s.push('d'),
-                VirtualKeyCode::E => s.push('e'),
-                VirtualKeyCode::F => s.push('f'),
-                VirtualKeyCode::G => s.push('g'),
-                VirtualKeyCode::H => s.push('h'),
-                VirtualKeyCode::I => s.push('i'),
-                VirtualKeyCode::J => s.push('j'),
-                VirtualKeyCode::K => s.push('k'),
-                VirtualKeyCode::L => s.push('l'),
-                VirtualKeyCode::M => s.push('m'),
-                VirtualKeyCode::N => s.push('n'),
-                VirtualKeyCode::O => s.push('o'),
-                VirtualKeyCode::P => s.push('p'),
-                VirtualKeyCode::Q => s.push('q'),
-                VirtualKeyCode::R => s.push('r'),
-                VirtualKeyCode::S => s.push('s'),
-                VirtualKeyCode::T => s.push('t'),
-                VirtualKeyCode::U => s.push('u'),
-                VirtualKeyCode::V => s.push('v'),
-                VirtualKeyCode::W => s.push('w'),
-                VirtualKeyCode::X => s.push('x'),
-                VirtualKeyCode::Y => s.push('y'),
-                VirtualKeyCode::Z => s.push('z'),
-                VirtualKeyCode::Key1 => s.push('1'),
-                VirtualKeyCode::Key2 => s.push('2'),
-                VirtualKeyCode::Key3 => s.push('3'),
-                VirtualKeyCode::Key4 => s.push('4'),
-                VirtualKeyCode::Key5 => s.push('5'),
-                VirtualKeyCode::Key6 => s.push('6'),
-                VirtualKeyCode::Key7 => s.push('7'),
-                VirtualKeyCode::Key8 => s.push('8'),
-                VirtualKeyCode::Key9 => s.push('9'),
-                VirtualKeyCode::Key0 => s.push('0'),
-                VirtualKeyCode::Space => s.push(' '),
-                VirtualKeyCode::Back => s.push('\x08'),
-                VirtualKeyCode::Return => s.push('\n'),
-                _ => {}
+
+        if alternate && save_cursor {
+            self.saved_cursor = self.cursor;
+        }
+
+        std::mem::swap(&mut self.visible_grid, &mut self.other_grid);
+        std::mem::swap(&mut self.keyboard_modes, &mut self.other_keyboard_modes);
+        self.modes.alternate_screen = alternate;
+
+        if alternate {
+            self.visible_grid.clear(true);
+        } else if save_cursor {
+            self.cursor = self.saved_cursor;
+        }
+        self.prune_images();
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.send_input(paste::encode(text, self.modes.bracketed_paste));
+    }
+
+    // whether drawing is held off for synchronized output, which gives up after `timeout` in
+    // case the program never turns it off
+    pub fn rendering_suspended(&self, timeout: Duration) -> bool {
+        self.modes.synchronized_output && self.synchronized_since.elapsed() < timeout
+    }
+
+    // the DECRPM state of a private mode: 1 when set, 2 when reset and 0 when not recognized
+    pub fn private_mode_state(&self, mode: u16) -> u8 {
+        let modes = &self.modes;
+        let state = match mode {
+            1 => modes.application_cursor,
+            7 => modes.autowrap,
+            25 => self.visible_cursor,
+            9 => modes.mouse == MouseMode::X10,
+            1000 => modes.mouse == MouseMode::Press,
+            1002 => modes.mouse == MouseMode::Drag,
+            1003 => modes.mouse == MouseMode::Motion,
+            1004 => modes.focus_reporting,
+            1005 => modes.mouse_encoding == MouseEncoding::Utf8,
+            1006 => modes.mouse_encoding == MouseEncoding::Sgr,
+            1015 => modes.mouse_encoding == MouseEncoding::Urxvt,
+            1007 => modes.alternate_scroll,
+            47 | 1047 | 1049 => modes.alternate_screen,
+            2004 => modes.bracketed_paste,
+            2026 => modes.synchronized_output,
+            _ => return 0,
+        };
+
+        if state {
+            1
+        } else {
+            2
+        }
+    }
+
+    // tells the program the window gained or lost focus if it asked to know
+    pub fn focus_changed(&mut self, focused: bool) {
+        // the releases of keys let go while unfocused never arrive
+        if !focused {
+            self.pressed_keys.clear();
+        }
+
+        if self.modes.focus_reporting {
+            let report = if focused { "\x1b[I" } else { "\x1b[O" };
+            self.reply(report.to_string());
+        }
+    }
+
+    // DECSET and DECRST
+    pub fn set_private_mode(&mut self, mode: u16, state: bool) {
+        let modes = &mut self.modes;
+
+        match mode {
+            1 => modes.application_cursor = state,
+            7 => modes.autowrap = state,
+            25 => self.visible_cursor = state,
+            9 | 1000 | 1002 | 1003 => {
+                modes.mouse = match (state, mode) {
+                    (false, _) => MouseMode::Off,
+                    (true, 9) => MouseMode::X10,
+                    (true, 1000) => MouseMode::Press,
+                    (true, 1002) => MouseMode::Drag,
+                    _ => MouseMode::Motion,
+                };
             }
+            1005 | 1006 | 1015 => {
+                let encoding = match mode {
+                    1005 => MouseEncoding::Utf8,
+                    1006 => MouseEncoding::Sgr,
+                    _ => MouseEncoding::Urxvt,
+                };
+
+                if state {
+                    modes.mouse_encoding = encoding;
+                } else if modes.mouse_encoding == encoding {
+                    modes.mouse_encoding = MouseEncoding::Default;
+                }
+            }
+            1004 => modes.focus_reporting = state,
+            1007 => modes.alternate_scroll = state,
+            2026 => {
+                // the timeout counts from when drawing was first held off, setting it again
+                // mustn't hold it off any longer
+                if state && !modes.synchronized_output {
+                    self.synchronized_since = Instant::now();
+                }
+                modes.synchronized_output = state;
+            }
+            2004 => modes.bracketed_paste = state,
+            47 | 1047 => self.set_alternate_screen(state, false),
+            1049 => self.set_alternate_screen(state, true),
+            _ => {}
         }
+    }
 
-        if self.modifiers.contains(winit::event::ModifiersState::SHIFT) {
-            s = s.to_uppercase();
+    // scrolls the view to the previous or next prompt, relative to the top of the screen
+    pub fn scroll_to_prompt(&mut self, previous: bool) {
+        let grid = &mut self.visible_grid;
+        let top = grid.screen_line(0) - grid.display_offset;
+
+        let prompts = grid.lines_with_mark(PromptMark::PromptStart);
+        let target = if previous {
+            prompts.into_iter().rev().find(|&line| line < top)
+        } else {
+            prompts.into_iter().find(|&line| line > top)
+        };
+
+        match target {
+            Some(line) => grid.scroll_to_line(line),
+            None if !previous => grid.display_offset = 0,
+            None => {}
         }
+    }
+
+    // the output of the most recent command, from its OSC 133 C mark up to its D mark
+    pub fn last_command_output(&self) -> Option<String> {
+        let grid = &self.visible_grid;
+
+        let start_line = *grid.lines_with_mark(PromptMark::OutputStart).last()?;
+        let start_col = grid
+            .line(start_line)
+            .metadata
+            .mark_column(PromptMark::OutputStart)?;
+
+        let end = (start_line..grid.total_lines()).find_map(|line| {
+            grid.line(line)
+                .metadata
+                .marks
+                .iter()
+                .find(|(col, mark)| {
+                    matches!(mark, PromptMark::CommandEnd(_))
+                        && (line > start_line || *col >= start_col)
+                })
+                .map(|(col, _)| (*col, line))
+        });
+        let end = end.unwrap_or((self.cursor.0, grid.screen_line(self.cursor.1)));
+
+        let text = grid.text_between((start_col, start_line), end);
+        Some(text.trim_end_matches('\n').to_string())
+    }
 
-        s
+    // the kitty keyboard protocol flags in effect
+    pub fn keyboard_flags(&self) -> u32 {
+        self.keyboard_modes.last().copied().unwrap_or(0)
+    }
+
+    pub fn push_keyboard_mode(&mut self, flags: u32) {
+        // like kitty, a program that keeps pushing loses its oldest entries
+        if self.keyboard_modes.len() >= MAX_KEYBOARD_MODES {
+            self.keyboard_modes.remove(0);
+        }
+        self.keyboard_modes.push(flags);
+    }
+
+    pub fn pop_keyboard_mode(&mut self, count: u32) {
+        let len = self.keyboard_modes.len().saturating_sub(count as usize);
+        self.keyboard_modes.truncate(len);
+    }
+
+    // replaces the flags in effect with `flags`, or adds or removes them
+    pub fn set_keyboard_mode(&mut self, flags: u32, mode: u8) {
+        let current = self.keyboard_flags();
+        let flags = match mode {
+            2 => current | flags,
+            3 => current & !flags,
+            _ => flags,
+        };
+
+        match self.keyboard_modes.last_mut() {
+            Some(last) => *last = flags,
+            None => self.keyboard_modes.push(flags),
+        }
+    }
+
+    // returns whether the key was sent, in which case the text it produces shouldn't be
+    pub fn key_pressed(&mut self, key: &winit::event::KeyboardInput) -> bool {
+        let kind = match key.state {
+            winit::event::ElementState::Pressed if self.pressed_keys.insert(key.scancode) => {
+                KeyEventKind::Press
+            }
+            winit::event::ElementState::Pressed => KeyEventKind::Repeat,
+            // a key whose press went to a keybinding has its release dropped too
+            winit::event::ElementState::Released if self.pressed_keys.remove(&key.scancode) => {
+                KeyEventKind::Release
+            }
+            winit::event::ElementState::Released => return false,
+        };
+
+        let flags = self.keyboard_flags();
+        let event = KeyEvent {
+            key: key.virtual_keycode,
+            modifiers: self.modifiers,
+            kind,
+            text: None,
+        };
+
+        if flags == 0 {
+            if kind == KeyEventKind::Release {
+                return false;
+            }
+
+            // modifyOtherKeys reports the character the layout produced, so keys that produce
+            // text wait for it
+            if self.modes.modify_other_keys > 0 && keyboard::produces_text(key.virtual_keycode) {
+                self.flush_pending_key();
+                self.pending_key = Some(event);
+                return false;
+            }
+
+            return match key
+                .virtual_keycode
+                .and_then(|keycode| keyboard::encode(keycode, self.modifiers, &self.modes))
+            {
+                Some(text) => {
+                    self.send_input(text);
+                    true
+                }
+                None => false,
+            };
+        }
+
+        self.flush_pending_key();
+
+        if kind != KeyEventKind::Release && keyboard::produces_text(key.virtual_keycode) {
+            self.pending_key = Some(event);
+            return false;
+        }
+
+        if let Some(text) = keyboard::encode_kitty(&event, flags, &self.modes) {
+            self.send_input(text);
+        }
+        true
+    }
+
+    // text typed with the keyboard layout applied
+    pub fn received_character(&mut self, c: char) {
+        if let Some(mut event) = self.pending_key.take() {
+            event.text = Some(c).filter(|c| !c.is_control());
+            if let Some(text) = self.encode_pending(&event) {
+                self.send_input(text);
+            }
+            return;
+        }
+
+        if let Some(text) = keyboard::encode_char(c, self.modifiers) {
+            self.send_input(text);
+        }
+    }
+
+    // text typed some other way than with keys, like through an input method, whose keys were
+    // never sent
+    pub fn commit_text(&mut self, text: &str) {
+        self.preedit.clear();
+        self.pending_key = None;
+        self.send_input(text.to_string());
+    }
+
+    // sends a key that never got a character, like Ctrl with a letter on some platforms
+    pub fn flush_pending_key(&mut self) {
+        if let Some(event) = self.pending_key.take() {
+            if let Some(text) = self.encode_pending(&event) {
+                self.send_input(text);
+            }
+        }
+    }
+
+    // a key that waited for its character, with the kitty protocol if it's on
+    fn encode_pending(&self, event: &KeyEvent) -> Option<String> {
+        match self.keyboard_flags() {
+            0 => keyboard::encode_typed(event, &self.modes),
+            flags => keyboard::encode_kitty(event, flags, &self.modes),
+        }
+    }
+
+    fn send_input(&mut self, text: String) {
+        self.visible_grid.display_offset = 0;
+        self.reply(text);
+    }
+
+    // sends text to the program, which is dropped if the pty is already gone
+    pub fn reply(&self, text: String) {
+        let _ = self.transmitter.send(text);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use nix::libc::TIOCGWINSZ;
+
+    use super::*;
+
+    #[test]
+    fn test_report_size() {
+        let layout = Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32);
+        let mut terminal = Terminal::new(layout);
+        let master_fd = terminal.master_fd;
+
+        let size = || {
+            let mut size = winsize {
+                ws_row: 0,
+                ws_col: 0,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            unsafe { ioctl(master_fd, TIOCGWINSZ, &mut size) };
+            (size.ws_col as u32, size.ws_row as u32)
+        };
+        assert_eq!(size(), (terminal.width, terminal.height));
+
+        terminal.resize(winit::dpi::PhysicalSize::new(8 * 20 + 5, 16 * 10));
+        terminal.report_size(Duration::from_secs(60));
+        assert_ne!(size(), (terminal.width, terminal.height));
+
+        terminal.report_size(Duration::ZERO);
+        assert_eq!(size(), (terminal.width, terminal.height));
+    }
+
+    #[test]
+    fn test_title_directory() {
+        let layout = Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32);
+        let mut terminal = Terminal::new(layout);
+
+        terminal.window_title("{title}", false);
+        assert_eq!(terminal.looked_up_dir, None);
+
+        terminal.window_title("{cwd}", false);
+        let (looked_up_at, _) = terminal.looked_up_dir.clone().unwrap();
+        terminal.window_title("{cwd}", false);
+        assert_eq!(terminal.looked_up_dir.unwrap().0, looked_up_at);
+    }
+
+    #[test]
+    fn test_child_exited() {
+        let layout = Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32);
+        let mut terminal = Terminal::with_launch(layout, LaunchSpec::new("sh"));
+
+        // input sent before the shell is up can be lost, so wait for its prompt
+        terminal
+            .reciever
+            .recv_timeout(Duration::from_secs(30))
+            .unwrap();
+        terminal.transmitter.send("exit 3\n".to_string()).unwrap();
+
+        let start = Instant::now();
+        while terminal.child_exited().is_none() && start.elapsed() < Duration::from_secs(30) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(terminal.exit_status, Some(ChildExit::Code(3)));
+
+        // input after the exit is dropped rather than failing
+        terminal.transmitter.send("ls\n".to_string()).unwrap();
+
+        terminal.restart();
+        assert_eq!(terminal.exit_status, None);
+
+        // a reader that stopped without sending an exit still counts as one
+        let (_, exit_reciever) = std::sync::mpsc::channel();
+        terminal.exit_reciever = exit_reciever;
+        assert_eq!(terminal.child_exited(), Some(ChildExit::Lost));
+        assert_eq!(terminal.child_exited(), None);
+    }
+
+    #[test]
+    fn test_decode_output() {
+        let mut incomplete = vec![];
+        let euro = "€".as_bytes();
+
+        assert_eq!(
+            decode_output(&mut incomplete, &[b'a', euro[0], euro[1]]),
+            "a"
+        );
+        assert_eq!(incomplete, &euro[..2]);
+        assert_eq!(decode_output(&mut incomplete, &[euro[2], b'b']), "€b");
+        assert!(incomplete.is_empty());
+
+        assert_eq!(decode_output(&mut incomplete, b"a\xffb\xe2"), "a\u{fffd}b");
+        assert_eq!(decode_output(&mut incomplete, b"c"), "\u{fffd}c");
+
+        let layout = Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32);
+        let mut launch = LaunchSpec::new("sh");
+        launch.args = vec!["-c".to_string(), "printf 'a\\377b'".to_string()];
+        let mut terminal = Terminal::with_launch(layout, launch);
+
+        let mut output = String::new();
+        let start = Instant::now();
+        while terminal.child_exited().is_none() && start.elapsed() < Duration::from_secs(30) {
+            output.extend(terminal.reciever.try_iter());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        output.extend(terminal.reciever.try_iter());
+        assert_eq!(output, "a\u{fffd}b");
+        assert_eq!(terminal.exit_status, Some(ChildExit::Code(0)));
+    }
+
+    #[test]
+    fn test_launch_spec() {
+        let mut launch = LaunchSpec::new("/bin/sh");
+        launch.login = true;
+        launch.env_remove.push("COLORTERM".to_string());
+        assert_eq!(launch.argv()[0].to_str(), Ok("-sh"));
+        assert!(launch
+            .env()
+            .contains(&("TERM".to_string(), "xterm-256color".to_string())));
+        assert!(!launch.env().iter().any(|(key, _)| key == "COLORTERM"));
+
+        launch.cwd = Some("/tmp".into());
+        let pwd = launch.env().into_iter().filter(|(key, _)| key == "PWD");
+        assert_eq!(
+            pwd.collect::<Vec<_>>(),
+            [("PWD".to_string(), "/tmp".to_string())]
+        );
+
+        let layout = Layout::new(1.0, 16.0, (16 * 5) as f32, (8 * 5 + 5) as f32);
+        let mut launch = LaunchSpec::new("sh");
+        launch.args = vec![
+            "-c".to_string(),
+            "printf '%s:%s:%s' \"$TERM_PROGRAM\" \"$PWD\" \"$GREETING\"".to_string(),
+        ];
+        launch.env.push(("GREETING".to_string(), "hi".to_string()));
+        launch.cwd = Some("/".into());
+        let mut terminal = Terminal::with_launch(layout, launch);
+
+        let mut output = String::new();
+        let start = Instant::now();
+        while terminal.child_exited().is_none() && start.elapsed() < Duration::from_secs(30) {
+            output.extend(terminal.reciever.try_iter());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        output.extend(terminal.reciever.try_iter());
+        assert_eq!(output, "nyat:/:hi");
+        assert_eq!(terminal.exit_status, Some(ChildExit::Code(0)));
+    }
+}